@@ -0,0 +1,289 @@
+use std::time::Instant;
+
+use scorched_earth_core::{Board, GameOutcome, Move, PlayerId};
+
+use crate::{Budget, Evaluator, TranspositionTable};
+
+// Bigger than any evaluation, so won and lost positions always beat anything else
+const WIN: i32 = 1_000_000;
+
+// Deepest search a time budget will try, so a finished game tree doesn't loop forever
+const MAX_DEPTH: u32 = 64;
+
+// How many positions the transposition table remembers
+const TABLE_SIZE: usize = 1 << 16;
+
+// What an earlier search found out about a position
+#[derive(Clone, Copy)]
+struct Entry {
+    // How far ahead the position was searched
+    depth: u32,
+    score: i32,
+    bound: Bound,
+    // The move that did best (or caused a cutoff), worth trying first next time
+    best: Option<Move>,
+}
+
+// Alpha-beta only finds exact scores for some positions, and just a limit on the rest
+#[derive(Clone, Copy)]
+enum Bound {
+    Exact,
+    // The real score is at least this
+    Lower,
+    // The real score is at most this
+    Upper,
+}
+
+// Pick a move for the player using alpha-beta search with a custom evaluation. With more than two
+// players the search is paranoid: every opponent is assumed to be playing against this player,
+// and every teammate with them.
+// Returns None if the player has no legal moves.
+pub fn choose_move_with(
+    board: &Board,
+    player: PlayerId,
+    budget: Budget,
+    evaluator: &impl Evaluator,
+) -> Option<Move> {
+    let mut moves = board.legal_moves(player);
+    if moves.len() <= 1 {
+        return moves.pop();
+    }
+
+    let (max_depth, deadline) = match budget {
+        Budget::Depth(depth) => (depth.max(1), None),
+        Budget::Time(duration) => (MAX_DEPTH, Some(Instant::now() + duration)),
+    };
+
+    let mut search = Search {
+        board: board.clone(),
+        root: player,
+        evaluator,
+        deadline,
+        table: TranspositionTable::new(TABLE_SIZE),
+    };
+
+    // Iterative deepening: search one move ahead, then two, and so on, keeping the result of the
+    // last search that finished. Each search tries the best move from the one before first, which
+    // makes the cutoffs a lot more effective.
+    let mut best = moves[0];
+    for depth in 1..=max_depth {
+        let Some((m, score)) = search.root_search(&moves, depth) else { break };
+        best = m;
+
+        if let Some(i) = moves.iter().position(|&other| other == m) {
+            moves[..=i].rotate_right(1);
+        }
+
+        // No point looking further ahead once the result is certain
+        if score.abs() >= WIN - MAX_DEPTH as i32 {
+            break;
+        }
+    }
+
+    Some(best)
+}
+
+struct Search<'a, E> {
+    board: Board,
+    // The player the bot is choosing a move for
+    root: PlayerId,
+    evaluator: &'a E,
+    deadline: Option<Instant>,
+    table: TranspositionTable<Entry>,
+}
+
+impl<E: Evaluator> Search<'_, E> {
+    // The best move and its score, or None if time ran out before the search finished
+    fn root_search(&mut self, moves: &[Move], depth: u32) -> Option<(Move, i32)> {
+        let mut alpha = -WIN - 1;
+        let mut best = moves[0];
+
+        for &m in moves {
+            let res = self.board.make_move(self.root, m).ok()?;
+            let score = self.alpha_beta(depth - 1, 1, alpha, WIN + 1);
+            self.board.unmake_move(res.undo);
+
+            let score = score?;
+            if score > alpha {
+                alpha = score;
+                best = m;
+            }
+        }
+
+        Some((best, alpha))
+    }
+
+    // The score of the current position from the root player's point of view, or None if time
+    // ran out
+    fn alpha_beta(&mut self, depth: u32, ply: u32, mut alpha: i32, mut beta: i32) -> Option<i32> {
+        if self.deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            return None;
+        }
+
+        // Finding a win sooner (or a loss later) is better, so adjust by how far away it is
+        match self.board.outcome {
+            GameOutcome::Win { .. } | GameOutcome::TeamWin { .. }
+                if self.board.is_winner(self.root) =>
+            {
+                return Some(WIN - ply as i32)
+            }
+            GameOutcome::Win { .. } | GameOutcome::TeamWin { .. } => {
+                return Some(-WIN + ply as i32)
+            }
+            GameOutcome::Draw { .. } => return Some(0),
+            GameOutcome::Ongoing => {}
+        }
+
+        // Teammates can still win after the root player is out
+        if self.board.is_eliminated(self.root) && self.board.players()[self.root].team.is_none() {
+            return Some(-WIN + ply as i32);
+        }
+
+        if depth == 0 {
+            return Some(self.evaluator.evaluate(&self.board, self.root));
+        }
+
+        let hash = self.board.hash();
+        let (original_alpha, original_beta) = (alpha, beta);
+        let mut moves = self.board.legal_moves(self.board.turn());
+
+        if let Some(entry) = self.table.get(hash).copied() {
+            if entry.depth >= depth {
+                let score = from_table(entry.score, ply);
+                match entry.bound {
+                    Bound::Exact => return Some(score),
+                    Bound::Lower => alpha = alpha.max(score),
+                    Bound::Upper => beta = beta.min(score),
+                }
+                if alpha >= beta {
+                    return Some(score);
+                }
+            }
+
+            if let Some(i) = entry.best.and_then(|best| moves.iter().position(|&m| m == best)) {
+                moves.swap(0, i);
+            }
+        }
+
+        let player = self.board.turn();
+        let maximizing = player == self.root || self.board.are_teammates(player, self.root);
+        let mut best = None;
+
+        for m in moves {
+            let res = self.board.make_move(player, m).ok()?;
+            let score = self.alpha_beta(depth - 1, ply + 1, alpha, beta);
+            self.board.unmake_move(res.undo);
+            let score = score?;
+
+            if maximizing && score > alpha {
+                alpha = score;
+                best = Some(m);
+            } else if !maximizing && score < beta {
+                beta = score;
+                best = Some(m);
+            }
+
+            if alpha >= beta {
+                break;
+            }
+        }
+
+        let score = if maximizing { alpha } else { beta };
+        let bound = if score <= original_alpha {
+            Bound::Upper
+        } else if score >= original_beta {
+            Bound::Lower
+        } else {
+            Bound::Exact
+        };
+
+        self.table.insert(
+            hash,
+            Entry {
+                depth,
+                score: to_table(score, ply),
+                bound,
+                best,
+            },
+        );
+
+        Some(score)
+    }
+}
+
+// Win and loss scores count how far away they are from the root, but the same position can be
+// reached at different distances, so they're stored relative to the position itself
+fn to_table(score: i32, ply: u32) -> i32 {
+    if score >= WIN - MAX_DEPTH as i32 {
+        score + ply as i32
+    } else if score <= -WIN + MAX_DEPTH as i32 {
+        score - ply as i32
+    } else {
+        score
+    }
+}
+
+fn from_table(score: i32, ply: u32) -> i32 {
+    if score >= WIN - MAX_DEPTH as i32 {
+        score - ply as i32
+    } else if score <= -WIN + MAX_DEPTH as i32 {
+        score + ply as i32
+    } else {
+        score
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use scorched_earth_core::Direction;
+
+    use crate::{choose_move, Budget};
+
+    use super::*;
+
+    #[test]
+    fn takes_a_winning_capture() {
+        // Green can move two up onto Yellow and win straight away
+        let board: Board = "5x5 5./5./5./5./5. G@2.2,Y@2.0 G".parse().unwrap();
+        let winning_move = Move {
+            dir: Direction::Up,
+            len: 2,
+        };
+
+        assert_eq!(choose_move(&board, 0, Budget::Depth(1)), Some(winning_move));
+        assert_eq!(choose_move(&board, 0, Budget::Depth(4)), Some(winning_move));
+    }
+
+    #[test]
+    fn sees_a_capture_coming() {
+        // Two to the right or two down would leave Green in line with Yellow, who would land on
+        // them next move
+        let board: Board = "5x5 5./5./5./5./5. G@0.0,Y@2.2 G".parse().unwrap();
+        let m = choose_move(&board, 0, Budget::Depth(2)).unwrap();
+
+        let mut after = board.clone();
+        after.make_move(0, m).unwrap();
+        for reply in after.legal_moves(1) {
+            let mut board = after.clone();
+            board.make_move(1, reply).unwrap();
+            assert!(!board.is_eliminated(0), "{m:?} lets Yellow capture with {reply:?}");
+        }
+    }
+
+    #[test]
+    fn always_has_a_move_when_out_of_time() {
+        let board = Board::with_players(9, 9, 4);
+        let m = choose_move(&board, 0, Budget::Time(Duration::from_nanos(1))).unwrap();
+        assert!(board.legal_moves(0).contains(&m));
+    }
+
+    #[test]
+    fn no_move_without_legal_moves() {
+        // Green is boxed into the corner by scorched tiles
+        let board: Board = "3x3 .x./x2./3. G@0.0,Y@2.2 G".parse().unwrap();
+        assert!(board.legal_moves(0).is_empty());
+        assert_eq!(choose_move(&board, 0, Budget::Depth(3)), None);
+    }
+}
@@ -0,0 +1,70 @@
+use scorched_earth_core::{analysis, Board, PlayerId};
+
+// Scores a position from one player's point of view, where higher is better for them. Any
+// function or closure with the same signature works as an evaluator.
+pub trait Evaluator {
+    fn evaluate(&self, board: &Board, player: PlayerId) -> i32;
+}
+
+impl<F: Fn(&Board, PlayerId) -> i32> Evaluator for F {
+    fn evaluate(&self, board: &Board, player: PlayerId) -> i32 {
+        self(board, player)
+    }
+}
+
+// How many more moves the player has than their best-off opponent
+pub struct Mobility;
+
+impl Evaluator for Mobility {
+    fn evaluate(&self, board: &Board, player: PlayerId) -> i32 {
+        relative_to_opponents(board, player, |id| board.legal_moves(id).len() as i32)
+    }
+}
+
+// How many more tiles the player could eventually get to than their best-off opponent
+pub struct ReachableArea;
+
+impl Evaluator for ReachableArea {
+    fn evaluate(&self, board: &Board, player: PlayerId) -> i32 {
+        relative_to_opponents(board, player, |id| {
+            analysis::reachable_area(board, id).count() as i32
+        })
+    }
+}
+
+// A mix of mobility and reachable area
+pub struct Weighted {
+    pub mobility: i32,
+    pub area: i32,
+}
+
+impl Default for Weighted {
+    fn default() -> Self {
+        Weighted {
+            mobility: 1,
+            area: 2,
+        }
+    }
+}
+
+impl Evaluator for Weighted {
+    fn evaluate(&self, board: &Board, player: PlayerId) -> i32 {
+        self.mobility * Mobility.evaluate(board, player)
+            + self.area * ReachableArea.evaluate(board, player)
+    }
+}
+
+// The best score of the player and any teammates still in the game, minus the best score of any
+// opponent still in the game
+fn relative_to_opponents(board: &Board, player: PlayerId, score: impl Fn(PlayerId) -> i32) -> i32 {
+    let best = |on_side: bool| {
+        board
+            .active_players()
+            .filter(|&id| (id == player || board.are_teammates(id, player)) == on_side)
+            .map(&score)
+            .max()
+            .unwrap_or(0)
+    };
+
+    best(true) - best(false)
+}
@@ -0,0 +1,28 @@
+use std::time::Duration;
+
+use scorched_earth_core::{Board, Move, PlayerId};
+
+mod alpha_beta;
+pub mod eval;
+mod mcts;
+mod transposition;
+
+pub use alpha_beta::choose_move_with;
+pub use eval::{Evaluator, Mobility, ReachableArea, Weighted};
+pub use mcts::{choose_move_mcts, mcts_stats, MctsConfig, MoveStats};
+pub use transposition::TranspositionTable;
+
+// How much thinking the bot is allowed to do for one move
+#[derive(Clone, Copy, Debug)]
+pub enum Budget {
+    // Search this many moves ahead (counting every player's moves)
+    Depth(u32),
+    // Search deeper and deeper until the time runs out
+    Time(Duration),
+}
+
+// Pick a move for the player using alpha-beta search with the default evaluation. Returns None if
+// the player has no legal moves.
+pub fn choose_move(board: &Board, player: PlayerId, budget: Budget) -> Option<Move> {
+    choose_move_with(board, player, budget, &Weighted::default())
+}
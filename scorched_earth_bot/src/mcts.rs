@@ -0,0 +1,263 @@
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use scorched_earth_core::{Board, GameOutcome, Move, PlayerId, Undo};
+
+// Settings for Monte Carlo tree search
+#[derive(Clone, Copy, Debug)]
+pub struct MctsConfig {
+    // How many random games to play out before picking a move
+    pub playouts: u32,
+    // How much to favour trying moves that haven't been looked at much over moves that have done
+    // well so far
+    pub exploration: f64,
+    // The same seed on the same board always gives the same result
+    pub seed: u64,
+}
+
+impl Default for MctsConfig {
+    fn default() -> Self {
+        MctsConfig {
+            playouts: 2000,
+            exploration: std::f64::consts::SQRT_2,
+            seed: 0,
+        }
+    }
+}
+
+// How one of the possible moves did during the search
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MoveStats {
+    pub mv: Move,
+    // How many playouts started with this move
+    pub visits: u32,
+    // Average reward for the player making the move, from 0 (always lost) to 1 (always won)
+    pub score: f64,
+}
+
+// Search the position and return stats for each of the player's legal moves, most visited first.
+// Every player tries to do the best for themselves, so this works for any number of players.
+pub fn mcts_stats(board: &Board, player: PlayerId, config: &MctsConfig) -> Vec<MoveStats> {
+    if board.outcome != GameOutcome::Ongoing || board.turn() != player {
+        return Vec::new();
+    }
+
+    let mut search = Search {
+        board: board.clone(),
+        rng: StdRng::seed_from_u64(config.seed),
+        exploration: config.exploration,
+        nodes: Vec::new(),
+    };
+    search.add_node(None, player);
+
+    for _ in 0..config.playouts {
+        search.iterate();
+    }
+
+    let mut stats: Vec<MoveStats> = search.nodes[0]
+        .children
+        .iter()
+        .map(|&child| {
+            let node = &search.nodes[child];
+            MoveStats {
+                mv: node.mv.expect("only the root has no move"),
+                visits: node.visits,
+                score: node.rewards[player] / node.visits.max(1) as f64,
+            }
+        })
+        .collect();
+
+    stats.sort_by_key(|stats| std::cmp::Reverse(stats.visits));
+    stats
+}
+
+// Pick the move that got the most playouts. Returns None if the player has no legal moves.
+pub fn choose_move_mcts(board: &Board, player: PlayerId, config: &MctsConfig) -> Option<Move> {
+    mcts_stats(board, player, config)
+        .first()
+        .map(|stats| stats.mv)
+}
+
+struct Node {
+    // The move that led here, and who made it
+    mv: Option<Move>,
+    mover: PlayerId,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    // Moves from here that don't have a child node yet
+    untried: Vec<Move>,
+    visits: u32,
+    // Total reward from playouts through here, for each player
+    rewards: Vec<f64>,
+}
+
+struct Search {
+    board: Board,
+    rng: StdRng,
+    exploration: f64,
+    nodes: Vec<Node>,
+}
+
+impl Search {
+    // Add a node for the board's current position
+    fn add_node(&mut self, parent: Option<(usize, Move)>, mover: PlayerId) -> usize {
+        let untried = if self.board.outcome == GameOutcome::Ongoing {
+            self.board.legal_moves(self.board.turn())
+        } else {
+            Vec::new()
+        };
+
+        let index = self.nodes.len();
+        self.nodes.push(Node {
+            mv: parent.map(|(_, m)| m),
+            mover,
+            parent: parent.map(|(parent, _)| parent),
+            children: Vec::new(),
+            untried,
+            visits: 0,
+            rewards: vec![0.0; self.board.players().len()],
+        });
+
+        if let Some((parent, _)) = parent {
+            self.nodes[parent].children.push(index);
+        }
+        index
+    }
+
+    // Walk down the tree, add one new node, play a random game from there and record the result
+    fn iterate(&mut self) {
+        let mut undos: Vec<Undo> = Vec::new();
+        let mut node = 0;
+
+        // Follow the most promising children until reaching a node with moves left to try
+        while self.nodes[node].untried.is_empty() && !self.nodes[node].children.is_empty() {
+            node = self.select_child(node);
+            let mv = self.nodes[node].mv.expect("only the root has no move");
+            undos.push(self.play(mv));
+        }
+
+        // Expand one of the untried moves
+        if !self.nodes[node].untried.is_empty() {
+            let i = self.rng.gen_range(0..self.nodes[node].untried.len());
+            let mv = self.nodes[node].untried.swap_remove(i);
+            let mover = self.board.turn();
+            undos.push(self.play(mv));
+            node = self.add_node(Some((node, mv)), mover);
+        }
+
+        // Play random moves until the game ends
+        while self.board.outcome == GameOutcome::Ongoing {
+            let moves = self.board.legal_moves(self.board.turn());
+            let Some(&mv) = moves.choose(&mut self.rng) else { break };
+            undos.push(self.play(mv));
+        }
+
+        let rewards = self.rewards();
+
+        for undo in undos.into_iter().rev() {
+            self.board.unmake_move(undo);
+        }
+
+        let mut current = Some(node);
+        while let Some(i) = current {
+            let node = &mut self.nodes[i];
+            node.visits += 1;
+            for (total, reward) in node.rewards.iter_mut().zip(&rewards) {
+                *total += reward;
+            }
+            current = node.parent;
+        }
+    }
+
+    // The child with the best upper confidence bound for the player choosing between them
+    fn select_child(&self, node: usize) -> usize {
+        let parent_visits = (self.nodes[node].visits.max(1) as f64).ln();
+
+        let bound = |child: usize| {
+            let child = &self.nodes[child];
+            let visits = child.visits.max(1) as f64;
+            child.rewards[child.mover] / visits
+                + self.exploration * (parent_visits / visits).sqrt()
+        };
+
+        self.nodes[node]
+            .children
+            .iter()
+            .copied()
+            .max_by(|&a, &b| bound(a).total_cmp(&bound(b)))
+            .expect("node has children")
+    }
+
+    fn play(&mut self, mv: Move) -> Undo {
+        let player = self.board.turn();
+        self.board
+            .make_move(player, mv)
+            .expect("legal moves can be made")
+            .undo
+    }
+
+    // Rewards for each player at the end of a playout. The winner (or everyone on the winning
+    // team) gets everything, and a draw is shared between everyone still in the game.
+    fn rewards(&self) -> Vec<f64> {
+        let mut rewards = vec![0.0; self.board.players().len()];
+
+        match self.board.outcome {
+            GameOutcome::Win { .. } | GameOutcome::TeamWin { .. } => {
+                for (id, reward) in rewards.iter_mut().enumerate() {
+                    if self.board.is_winner(id) {
+                        *reward = 1.0;
+                    }
+                }
+            }
+            GameOutcome::Draw { .. } | GameOutcome::Ongoing => {
+                let survivors: Vec<PlayerId> = (0..self.board.players().len())
+                    .filter(|&id| !self.board.is_eliminated(id))
+                    .collect();
+                for &id in &survivors {
+                    rewards[id] = 1.0 / survivors.len() as f64;
+                }
+            }
+        }
+
+        rewards
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use scorched_earth_core::Direction;
+
+    #[test]
+    fn same_seed_gives_same_stats() {
+        let board = Board::with_players(9, 9, 4);
+        let config = MctsConfig {
+            playouts: 500,
+            seed: 1,
+            ..Default::default()
+        };
+
+        let stats = mcts_stats(&board, board.turn(), &config);
+        assert!(!stats.is_empty());
+        assert_eq!(stats, mcts_stats(&board, board.turn(), &config));
+    }
+
+    #[test]
+    fn takes_a_winning_capture() {
+        // Green can move two up onto Yellow and win straight away
+        let board: Board = "5x5 5./5./5./5./5. G@2.2,Y@2.0 G".parse().unwrap();
+        let winning_move = Move {
+            dir: Direction::Up,
+            len: 2,
+        };
+
+        let stats = mcts_stats(&board, 0, &MctsConfig::default());
+        assert_eq!(stats[0].mv, winning_move);
+        assert_eq!(stats[0].score, 1.0);
+        assert_eq!(choose_move_mcts(&board, 0, &MctsConfig::default()), Some(winning_move));
+    }
+
+    #[test]
+    fn no_stats_when_its_not_the_players_turn() {
+        let board = Board::default();
+        assert!(mcts_stats(&board, 1, &MctsConfig::default()).is_empty());
+    }
+}
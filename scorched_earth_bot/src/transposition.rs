@@ -0,0 +1,35 @@
+// A fixed-size cache of results for positions, looked up by Board::hash. The same position often
+// comes up again through different move orders, so a search can reuse what it found last time.
+// When two positions land in the same slot the newer one replaces the older one.
+pub struct TranspositionTable<T> {
+    entries: Vec<Option<(u64, T)>>,
+}
+
+impl<T> TranspositionTable<T> {
+    // Make a table with room for at least this many positions (rounded up to a power of two)
+    pub fn new(capacity: usize) -> Self {
+        let mut entries = Vec::new();
+        entries.resize_with(capacity.max(1).next_power_of_two(), || None);
+        TranspositionTable { entries }
+    }
+
+    fn slot(&self, hash: u64) -> usize {
+        hash as usize & (self.entries.len() - 1)
+    }
+
+    pub fn get(&self, hash: u64) -> Option<&T> {
+        match &self.entries[self.slot(hash)] {
+            Some((stored, value)) if *stored == hash => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn insert(&mut self, hash: u64, value: T) {
+        let slot = self.slot(hash);
+        self.entries[slot] = Some((hash, value));
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.iter_mut().for_each(|entry| *entry = None);
+    }
+}
@@ -0,0 +1,70 @@
+use scorched_earth_core::{analysis, Board, ScorchState, Vector};
+
+// Read a position written in notation (or use the starting position) and print what the
+// analysis module makes of it
+fn main() {
+    let board: Board = match std::env::args().nth(1) {
+        Some(notation) => match notation.parse() {
+            Ok(board) => board,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                return;
+            }
+        },
+        None => Board::default(),
+    };
+
+    print_analysis(&board);
+}
+
+fn print_analysis(board: &Board) {
+    println!("{}", board.to_notation());
+
+    let voronoi = analysis::voronoi(board);
+    let territory = analysis::territory(board);
+    for (id, player) in board.players().iter().enumerate() {
+        println!(
+            "{:?}: reaches {} tiles, gets to {} first, has room for {}",
+            player.color,
+            analysis::reachable_area(board, id).count(),
+            voronoi[id].count(),
+            territory[id]
+        );
+    }
+    println!(
+        "{} regions, separated: {}",
+        analysis::regions(board).len(),
+        analysis::is_separated(board)
+    );
+
+    // Who gets to each tile first (by the first letter of their color, or + if it's a tie),
+    // then chokepoints as *, then how many moves the player to move needs to get to each tile
+    let chokepoints = analysis::chokepoints(board);
+    let distances = analysis::distance_map(board, board.turn());
+    let layout = board.layout();
+    for y in 0..board.height() as isize {
+        let mut rows = [String::new(), String::new(), String::new()];
+        for x in 0..board.width() as isize {
+            let pos = Vector { x, y };
+            let index = layout.index(pos).unwrap();
+            let blocked = match board.scorch_state(pos) {
+                Some(ScorchState::Scorched) => Some('x'),
+                Some(ScorchState::Wall) => Some('#'),
+                _ => None,
+            };
+
+            let owner = voronoi.iter().position(|owned| owned.get(index));
+            rows[0].push(blocked.unwrap_or_else(|| match owner {
+                Some(id) => format!("{:?}", board.players()[id].color).remove(0),
+                None => '+',
+            }));
+            rows[1].push(blocked.unwrap_or(if chokepoints.get(index) { '*' } else { '.' }));
+            rows[2].push(blocked.unwrap_or_else(|| match distances[index] {
+                Some(distance) => char::from_digit(distance as u32 % 36, 36).unwrap(),
+                None => '-',
+            }));
+        }
+        println!("{}", rows.join("   "));
+    }
+    println!();
+}
@@ -0,0 +1,22 @@
+use scorched_earth_core::Board;
+
+// Read a position written in notation (or use the starting position) and print it back out,
+// along with the moves the player to move can make
+fn main() {
+    let board: Board = match std::env::args().nth(1) {
+        Some(notation) => match notation.parse() {
+            Ok(board) => board,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                return;
+            }
+        },
+        None => Board::default(),
+    };
+
+    println!("{}", board.to_notation());
+    println!("{}", board.outcome_summary());
+    for m in board.legal_moves(board.turn()) {
+        println!("{:?} {}", m.dir, m.len);
+    }
+}
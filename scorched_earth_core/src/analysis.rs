@@ -0,0 +1,395 @@
+use crate::{
+    Bitboard, Board, DrawReason, GameOutcome, LossReason, PlayerId, ScorchRule, TileContents,
+    Vector,
+};
+
+// Working out which parts of the board players can still get to and how it's split up between
+// them, for the territory rule, bots, hints and looking back over a game. None of this changes
+// the board, and none of it takes turn order or other players getting in the way into account.
+
+// Every tile that could be landed on in one move from any of the from tiles, if only the open
+// tiles could be landed on, along with every tile the moves would pass through on the way. Without
+// jumping the whole path has to be open too, and even with it walls can't be jumped over.
+fn one_move(board: &Board, from: &Bitboard, open: &Bitboard) -> (Bitboard, Bitboard) {
+    let layout = board.layout();
+    let passable = if board.rules.jumping {
+        layout.all.and_not(&board.walls())
+    } else {
+        open.clone()
+    };
+
+    let mut landed = Bitboard::EMPTY;
+    let mut passed = Bitboard::EMPTY;
+    for &dir in board.directions() {
+        let mut tiles = from.clone();
+        for len in 1..=board.rules.max_move_len() {
+            tiles = layout.step(&tiles, dir.to_vector());
+            if board.rules.move_lengths.contains(&len) {
+                landed |= tiles.clone() & open;
+            }
+
+            tiles &= &passable;
+            passed |= &tiles;
+            if tiles.is_empty() {
+                break;
+            }
+        }
+    }
+    (landed, passed)
+}
+
+// Tiles that aren't scorched or walls. Other players' tiles count, since nothing here stops
+// anyone moving onto or through them.
+fn open_tiles(board: &Board) -> Bitboard {
+    board.layout().all.and_not(&board.blocked())
+}
+
+// Every open tile that could be reached from the start tiles in any number of moves, counting
+// the start tiles themselves, if nothing got scorched along the way. Any move can be made in
+// reverse, so whichever tile it starts from the same region gets filled.
+fn flood(board: &Board, start: Bitboard, open: &Bitboard) -> Bitboard {
+    let mut reached = start & open;
+    loop {
+        let next = one_move(board, &reached, open).0 | &reached;
+        if next == reached {
+            return reached;
+        }
+        reached = next;
+    }
+}
+
+// The tile a player who is still in the game is on
+fn start_tile(board: &Board, player: PlayerId) -> Option<usize> {
+    if board.is_eliminated(player) {
+        return None;
+    }
+    board.players.get(player).and_then(|p| board.layout().index(p.pos))
+}
+
+// The tiles a player could ever get to if they had the board to themselves, including the one
+// they're on and any that other players are on. Empty for players who are out.
+pub fn reachable_area(board: &Board, player: PlayerId) -> Bitboard {
+    match start_tile(board, player) {
+        Some(index) => flood(board, Bitboard::single(index), &open_tiles(board)),
+        None => Bitboard::EMPTY,
+    }
+}
+
+// The tiles a player could ever land on or pass over if they had the board to themselves, so
+// everything else might as well be a wall
+pub(crate) fn usable_area(board: &Board, player: PlayerId) -> Bitboard {
+    let reachable = reachable_area(board, player);
+    one_move(board, &reachable, &open_tiles(board)).1 | reachable
+}
+
+// The empty tiles split into regions that can't be reached from each other
+pub fn regions(board: &Board) -> Vec<Bitboard> {
+    let open = open_tiles(board);
+    let mut left = open.clone();
+    let mut regions = Vec::new();
+    loop {
+        let Some(index) = left.iter().next() else { return regions };
+        let region = flood(board, Bitboard::single(index), &open);
+        left = left.and_not(&region);
+        regions.push(region);
+    }
+}
+
+// How many moves it would take a player to land on each tile if they had the board to
+// themselves, indexed the same way as bitboards, or None for tiles they can't get to. Their own
+// tile is 0, and everything is None for players who are out.
+pub fn distance_map(board: &Board, player: PlayerId) -> Vec<Option<usize>> {
+    let layout = board.layout();
+    let mut distances = vec![None; layout.width * layout.height];
+    let Some(start) = start_tile(board, player) else { return distances };
+
+    // Spread out one move at a time, so each tile is found first at its shortest distance
+    let open = open_tiles(board);
+    let mut reached = Bitboard::single(start);
+    let mut frontier = reached.clone();
+    let mut distance = 0;
+    while !frontier.is_empty() {
+        for index in frontier.iter() {
+            distances[index] = Some(distance);
+        }
+        frontier = one_move(board, &frontier, &open).0.and_not(&reached);
+        reached |= &frontier;
+        distance += 1;
+    }
+    distances
+}
+
+// The tiles each player can land on before anyone else, indexed by player. Tiles that more than
+// one player can get to in the fewest moves aren't anyone's, and neither is anything nobody can
+// get to. Like distance_map, this doesn't take turn order or players getting in each other's
+// way into account.
+pub fn voronoi(board: &Board) -> Vec<Bitboard> {
+    let maps: Vec<Vec<Option<usize>>> = (0..board.players.len())
+        .map(|id| distance_map(board, id))
+        .collect();
+
+    let mut owned = vec![Bitboard::EMPTY; board.players.len()];
+    for index in board.layout().all.iter() {
+        let distances: Vec<(PlayerId, usize)> = maps
+            .iter()
+            .enumerate()
+            .filter_map(|(id, map)| map[index].map(|distance| (id, distance)))
+            .collect();
+        let Some(&(_, nearest)) = distances.iter().min_by_key(|&&(_, distance)| distance) else {
+            continue;
+        };
+
+        let mut closest = distances.iter().filter(|&&(_, distance)| distance == nearest);
+        if let (Some(&(owner, _)), None) = (closest.next(), closest.next()) {
+            owned[owner].set(index);
+        }
+    }
+    owned
+}
+
+// Empty tiles that are the only way between two parts of a region, so scorching one (or moving
+// off it) would split the region in two. These are the articulation points of the graph of tiles
+// joined by moves. Without jumping that includes tiles that are only ever moved over, which can
+// be in a different region from the tiles they join up.
+pub fn chokepoints(board: &Board) -> Bitboard {
+    let open = open_tiles(board);
+    let regions = regions(board);
+    let mut chokepoints = Bitboard::EMPTY;
+    for index in open.iter() {
+        let without = open.and_not(&Bitboard::single(index));
+        let splits = |region: &Bitboard| {
+            let rest = region.and_not(&Bitboard::single(index));
+            let Some(other) = rest.iter().next() else { return false };
+            flood(board, Bitboard::single(other), &without) != rest
+        };
+
+        if regions.iter().any(splits) {
+            chokepoints.set(index);
+        }
+    }
+    chokepoints
+}
+
+// Whether nobody left in the game can reach an opponent any more, so they're each playing on
+// their own part of the board (teammates can still share one)
+pub fn is_separated(board: &Board) -> bool {
+    let areas: Vec<Bitboard> = (0..board.players.len())
+        .map(|id| reachable_area(board, id))
+        .collect();
+
+    board.active_players().all(|id| {
+        // Jumping over a tile with the path rule scorches it, so that could still run over an
+        // opponent who moves there later
+        let mut reach = areas[id].clone();
+        if board.rules.jumping && board.rules.scorch == ScorchRule::Path {
+            reach |= one_move(board, &reach, &open_tiles(board)).1;
+        }
+
+        board.active_players().all(|other| {
+            other == id || board.are_teammates(id, other) || !reach.intersects(&areas[other])
+        })
+    })
+}
+
+// How many tiles each player still has room to move onto, leaving out tiles players are on.
+// Teammates pool their room, so each of them gets the size of the whole team's area.
+pub fn territory(board: &Board) -> Vec<usize> {
+    let areas: Vec<Bitboard> = (0..board.players.len())
+        .map(|id| reachable_area(board, id))
+        .collect();
+
+    (0..board.players.len())
+        .map(|id| {
+            if board.is_eliminated(id) {
+                return 0;
+            }
+
+            let mut area = areas[id].clone();
+            for other in board.active_players() {
+                if board.are_teammates(id, other) {
+                    area |= &areas[other];
+                }
+            }
+            area.and_not(&board.occupied()).count()
+        })
+        .collect()
+}
+
+impl Board {
+    // With the territory rule, end the game once everyone is separated from their opponents. The
+    // side with the most room wins and everyone else is eliminated, or it's a draw if more than
+    // one side has the most. Returns how much room each player had if the game ended.
+    pub(crate) fn score_territory(
+        &mut self,
+        eliminated: &mut Vec<(PlayerId, LossReason)>,
+        changes: &mut Vec<(Vector, TileContents)>,
+    ) -> Option<Vec<usize>> {
+        if !self.rules.territory || self.outcome != GameOutcome::Ongoing || !is_separated(self) {
+            return None;
+        }
+
+        let room = territory(self);
+        let most = self.active_players().map(|id| room[id]).max()?;
+        let best: Vec<PlayerId> = self.active_players().filter(|&id| room[id] == most).collect();
+
+        // Teammates have the same room, so it's only a tie if the best players aren't all on
+        // the same team
+        if best.iter().any(|&id| id != best[0] && !self.are_teammates(id, best[0])) {
+            self.outcome = GameOutcome::Draw {
+                reason: DrawReason::EqualRoom,
+            };
+            return Some(room);
+        }
+
+        let losers: Vec<PlayerId> = self
+            .active_players()
+            .filter(|&id| room[id] < most)
+            .collect();
+        for id in losers {
+            self.eliminate(id, LossReason::LessRoom, changes);
+            eliminated.push((id, LossReason::LessRoom));
+        }
+
+        Some(room)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Direction, Move};
+
+    fn tile(board: &Board, x: isize, y: isize) -> usize {
+        board.layout().index(Vector { x, y }).unwrap()
+    }
+
+    fn tiles(board: &Board, positions: &[(isize, isize)]) -> Bitboard {
+        let mut tiles = Bitboard::EMPTY;
+        for &(x, y) in positions {
+            tiles.set(tile(board, x, y));
+        }
+        tiles
+    }
+
+    #[test]
+    fn empty_board() {
+        let board = Board::default();
+
+        assert_eq!(reachable_area(&board, 0).count(), 121);
+        assert_eq!(regions(&board).len(), 1);
+        assert!(!is_separated(&board));
+        assert!(chokepoints(&board).is_empty());
+
+        // Moves are 1 or 2 tiles, so the far corner is 20 steps but only 10 moves away
+        let distances = distance_map(&board, 0);
+        assert_eq!(distances[tile(&board, 0, 0)], Some(0));
+        assert_eq!(distances[tile(&board, 2, 0)], Some(1));
+        assert_eq!(distances[tile(&board, 3, 0)], Some(2));
+        assert_eq!(distances[tile(&board, 10, 10)], Some(10));
+
+        // The players are in opposite corners, so they split the board along the diagonal
+        // between them, and the tiles on it are nobody's
+        let voronoi = voronoi(&board);
+        assert_eq!(voronoi[0].count(), 55);
+        assert_eq!(voronoi[1].count(), 55);
+        assert!(voronoi[0].get(tile(&board, 9, 0)));
+        assert!(voronoi[1].get(tile(&board, 10, 1)));
+        assert!(!voronoi[0].get(tile(&board, 10, 0)) && !voronoi[1].get(tile(&board, 10, 0)));
+    }
+
+    #[test]
+    fn one_gap_in_a_wall() {
+        let board: Board = "7x5 3.x3./3.x3./7./3.x3./3.x3. G@0.2,Y@6.2 G".parse().unwrap();
+
+        assert_eq!(reachable_area(&board, 0).count(), 31);
+        assert_eq!(reachable_area(&board, 0), reachable_area(&board, 1));
+        assert!(!is_separated(&board));
+
+        // The gap is the only way through, and so are the tiles either side of it, since
+        // moving two tiles at once still needs the tile in between to be empty
+        assert_eq!(chokepoints(&board), tiles(&board, &[(2, 2), (3, 2), (4, 2)]));
+
+        let distances = distance_map(&board, 0);
+        assert_eq!(distances[tile(&board, 3, 2)], Some(2));
+        assert_eq!(distances[tile(&board, 6, 2)], Some(3));
+        assert_eq!(distances[tile(&board, 6, 0)], Some(4));
+        assert_eq!(distances[tile(&board, 3, 0)], None);
+
+        let voronoi = voronoi(&board);
+        assert_eq!(voronoi[0].count(), 15);
+        assert_eq!(voronoi[1].count(), 15);
+        assert!(voronoi[0].get(tile(&board, 2, 4)));
+        assert!(voronoi[1].get(tile(&board, 4, 0)));
+        assert!(!voronoi[0].get(tile(&board, 3, 2)) && !voronoi[1].get(tile(&board, 3, 2)));
+    }
+
+    #[test]
+    fn split_board() {
+        let board: Board = "7x5 2.x4./2.x4./2.x4./2.x4./2.x4. G@0.0,Y@6.4 Y".parse().unwrap();
+
+        assert_eq!(reachable_area(&board, 0).count(), 10);
+        assert_eq!(reachable_area(&board, 1).count(), 20);
+        assert_eq!(regions(&board).len(), 2);
+        assert!(is_separated(&board));
+        assert_eq!(territory(&board), [9, 19]);
+        assert!(chokepoints(&board).is_empty());
+
+        let distances = distance_map(&board, 1);
+        assert_eq!(distances[tile(&board, 3, 0)], Some(4));
+        assert_eq!(distances[tile(&board, 0, 0)], None);
+
+        let voronoi = voronoi(&board);
+        assert_eq!(voronoi[0], reachable_area(&board, 0));
+        assert_eq!(voronoi[1], reachable_area(&board, 1));
+    }
+
+    #[test]
+    fn territory_rule_ends_the_game() {
+        // Green moving away from the gap scorches the tile next to it, which cuts the board in
+        // two and leaves Yellow with less room
+        let mut board: Board = "6x3 3.x2./6./3.x2. G@2.1,Y@5.1 G".parse().unwrap();
+        board.rules.territory = true;
+        let res = board
+            .make_move(
+                0,
+                Move {
+                    dir: Direction::Left,
+                    len: 1,
+                },
+            )
+            .unwrap();
+        assert_eq!(res.territory, Some(vec![7, 6]));
+        assert_eq!(res.eliminated, [(1, LossReason::LessRoom)]);
+        assert_eq!(
+            board.outcome,
+            GameOutcome::Win {
+                winner: 0,
+                reason: LossReason::LessRoom
+            }
+        );
+    }
+
+    #[test]
+    fn territory_rule_draw() {
+        // Like above, but both sides end up with the same room
+        let mut board: Board = "5x3 2.x2./5./2.x2. G@2.1,Y@4.1 G".parse().unwrap();
+        board.rules.territory = true;
+        let res = board
+            .make_move(
+                0,
+                Move {
+                    dir: Direction::Left,
+                    len: 1,
+                },
+            )
+            .unwrap();
+        assert!(res.eliminated.is_empty());
+        assert_eq!(
+            board.outcome,
+            GameOutcome::Draw {
+                reason: DrawReason::EqualRoom
+            }
+        );
+        assert_eq!(board.outcome_summary(), "draw, with the same room on each side");
+    }
+}
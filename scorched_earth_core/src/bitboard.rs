@@ -0,0 +1,541 @@
+use std::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Topology, Vector};
+
+const WORDS: usize = 16;
+
+// How many words fit in the bitboard itself, which is enough for boards of up to 128 tiles like
+// the default 11x11 one
+const INLINE_WORDS: usize = 2;
+
+// The most tiles a board can have, since every tile needs a bit
+pub const MAX_CELLS: usize = WORDS * 64;
+
+// A set of tiles stored as one bit per tile, indexed row by row (y * width + x)
+#[derive(Clone, PartialEq, Eq, Hash, Serialize, Deserialize, std::fmt::Debug)]
+#[serde(into = "Vec<u64>", try_from = "Vec<u64>")]
+pub struct Bitboard {
+    words: Words,
+}
+
+// Small sets are kept inline so boards stay cheap to clone, and only sets with tiles past the
+// first 128 go on the heap. Sets are always stored inline when they can be, so that equal sets
+// are stored the same way.
+#[derive(Clone, PartialEq, Eq, Hash, std::fmt::Debug)]
+enum Words {
+    Inline([u64; INLINE_WORDS]),
+    Boxed(Box<[u64; WORDS]>),
+}
+
+impl Default for Bitboard {
+    fn default() -> Self {
+        Self::EMPTY
+    }
+}
+
+impl Bitboard {
+    pub const EMPTY: Bitboard = Bitboard {
+        words: Words::Inline([0; INLINE_WORDS]),
+    };
+
+    pub fn single(index: usize) -> Self {
+        let mut bb = Self::EMPTY;
+        bb.set(index);
+        bb
+    }
+
+    // Make a bitboard from its words, lowest first, storing it inline if it fits
+    fn from_words(words: &[u64]) -> Self {
+        if words.iter().skip(INLINE_WORDS).all(|&word| word == 0) {
+            let mut inline = [0; INLINE_WORDS];
+            for (word, &src) in inline.iter_mut().zip(words) {
+                *word = src;
+            }
+            return Bitboard {
+                words: Words::Inline(inline),
+            };
+        }
+
+        let mut boxed = Box::new([0; WORDS]);
+        boxed[..words.len()].copy_from_slice(words);
+        Bitboard {
+            words: Words::Boxed(boxed),
+        }
+    }
+
+    fn words(&self) -> &[u64] {
+        match &self.words {
+            Words::Inline(words) => words,
+            Words::Boxed(words) => &words[..],
+        }
+    }
+
+    // A word of the set, which is 0 past the ones that are stored
+    fn word(&self, i: usize) -> u64 {
+        self.words().get(i).copied().unwrap_or(0)
+    }
+
+    // The inline words as one number, if the set is stored inline
+    fn inline(&self) -> Option<u128> {
+        match &self.words {
+            Words::Inline([low, high]) => Some(*low as u128 | (*high as u128) << 64),
+            Words::Boxed(_) => None,
+        }
+    }
+
+    fn from_inline(bits: u128) -> Self {
+        Bitboard {
+            words: Words::Inline([bits as u64, (bits >> 64) as u64]),
+        }
+    }
+
+    // Combine two sets word by word. The combination has to leave two empty words empty.
+    fn combine(&self, other: &Bitboard, f: impl Fn(u64, u64) -> u64) -> Self {
+        if let (Words::Inline(a), Words::Inline(b)) = (&self.words, &other.words) {
+            return Bitboard {
+                words: Words::Inline([f(a[0], b[0]), f(a[1], b[1])]),
+            };
+        }
+
+        let mut words = [0; WORDS];
+        for (i, word) in words.iter_mut().enumerate() {
+            *word = f(self.word(i), other.word(i));
+        }
+        Self::from_words(&words)
+    }
+
+    pub fn get(&self, index: usize) -> bool {
+        self.word(index / 64) & (1 << (index % 64)) != 0
+    }
+
+    pub fn set(&mut self, index: usize) {
+        match &mut self.words {
+            Words::Inline(words) if index / 64 < INLINE_WORDS => {
+                words[index / 64] |= 1 << (index % 64)
+            }
+            Words::Inline(words) => {
+                let mut boxed = Box::new([0; WORDS]);
+                boxed[..INLINE_WORDS].copy_from_slice(words);
+                boxed[index / 64] |= 1 << (index % 64);
+                self.words = Words::Boxed(boxed);
+            }
+            Words::Boxed(words) => words[index / 64] |= 1 << (index % 64),
+        }
+    }
+
+    pub fn clear(&mut self, index: usize) {
+        match &mut self.words {
+            Words::Inline(words) => {
+                if let Some(word) = words.get_mut(index / 64) {
+                    *word &= !(1 << (index % 64));
+                }
+            }
+            Words::Boxed(words) => {
+                words[index / 64] &= !(1 << (index % 64));
+                *self = Self::from_words(&words[..]);
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words().iter().all(|&word| word == 0)
+    }
+
+    pub fn count(&self) -> usize {
+        self.words().iter().map(|word| word.count_ones() as usize).sum()
+    }
+
+    // Indices of all the set bits, lowest first
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.words().iter().enumerate().flat_map(|(i, &word)| {
+            let mut word = word;
+            std::iter::from_fn(move || {
+                if word == 0 {
+                    return None;
+                }
+                let bit = word.trailing_zeros() as usize;
+                word &= word - 1;
+                Some(i * 64 + bit)
+            })
+        })
+    }
+
+    // Move every bit towards higher indices, dropping the ones that go past the end
+    pub fn shift_up(&self, amount: usize) -> Self {
+        // Most shifts are small and stay inline
+        if let Some(bits) = self.inline() {
+            if amount == 0 || (amount < 128 && bits >> (128 - amount) == 0) {
+                return Self::from_inline(bits << amount);
+            }
+        }
+
+        let (words, bits) = (amount / 64, amount % 64);
+        let mut result = [0; WORDS];
+        for i in (words..WORDS).rev() {
+            let src = i - words;
+            result[i] = self.word(src) << bits;
+            if bits > 0 && src > 0 {
+                result[i] |= self.word(src - 1) >> (64 - bits);
+            }
+        }
+        Self::from_words(&result)
+    }
+
+    // Move every bit towards lower indices, dropping the ones that go below zero
+    pub fn shift_down(&self, amount: usize) -> Self {
+        if let Some(bits) = self.inline() {
+            return Self::from_inline(bits.checked_shr(amount as u32).unwrap_or(0));
+        }
+
+        let (words, bits) = (amount / 64, amount % 64);
+        let mut result = [0; WORDS];
+        for (i, word) in result.iter_mut().take(WORDS.saturating_sub(words)).enumerate() {
+            let src = i + words;
+            *word = self.word(src) >> bits;
+            if bits > 0 && src + 1 < WORDS {
+                *word |= self.word(src + 1) << (64 - bits);
+            }
+        }
+        Self::from_words(&result)
+    }
+
+    // Whether any tile is in both sets
+    pub fn intersects(&self, other: &Bitboard) -> bool {
+        self.words()
+            .iter()
+            .zip(other.words())
+            .any(|(&a, &b)| a & b != 0)
+    }
+
+    pub fn and_not(&self, other: &Bitboard) -> Self {
+        self.combine(other, |a, b| a & !b)
+    }
+}
+
+impl From<Bitboard> for Vec<u64> {
+    fn from(bb: Bitboard) -> Self {
+        bb.words().to_vec()
+    }
+}
+
+impl TryFrom<Vec<u64>> for Bitboard {
+    type Error = &'static str;
+
+    fn try_from(words: Vec<u64>) -> Result<Self, Self::Error> {
+        if words.len() > WORDS {
+            return Err("bitboard is too big");
+        }
+        Ok(Self::from_words(&words))
+    }
+}
+
+impl BitAnd for Bitboard {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self {
+        self.combine(&rhs, |a, b| a & b)
+    }
+}
+
+impl BitAnd<&Bitboard> for Bitboard {
+    type Output = Self;
+
+    fn bitand(self, rhs: &Bitboard) -> Self {
+        self.combine(rhs, |a, b| a & b)
+    }
+}
+
+impl BitAndAssign<&Bitboard> for Bitboard {
+    fn bitand_assign(&mut self, rhs: &Bitboard) {
+        *self = self.combine(rhs, |a, b| a & b);
+    }
+}
+
+impl BitAndAssign for Bitboard {
+    fn bitand_assign(&mut self, rhs: Self) {
+        *self &= &rhs;
+    }
+}
+
+impl BitOr for Bitboard {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        self.combine(&rhs, |a, b| a | b)
+    }
+}
+
+impl BitOr<&Bitboard> for Bitboard {
+    type Output = Self;
+
+    fn bitor(self, rhs: &Bitboard) -> Self {
+        self.combine(rhs, |a, b| a | b)
+    }
+}
+
+impl BitOrAssign<&Bitboard> for Bitboard {
+    fn bitor_assign(&mut self, rhs: &Bitboard) {
+        *self = self.combine(rhs, |a, b| a | b);
+    }
+}
+
+impl BitOrAssign for Bitboard {
+    fn bitor_assign(&mut self, rhs: Self) {
+        *self |= &rhs;
+    }
+}
+
+// Masks for a particular board size, so bitboards can be shifted around without bits wrapping
+// from the end of one row onto the next
+#[derive(Clone, PartialEq, std::fmt::Debug)]
+pub struct Layout {
+    pub width: usize,
+    pub height: usize,
+    pub topology: Topology,
+    // Every tile on the board
+    pub all: Bitboard,
+    pub first_column: Bitboard,
+    pub last_column: Bitboard,
+    pub first_row: Bitboard,
+    pub last_row: Bitboard,
+}
+
+impl Layout {
+    pub fn new(width: usize, height: usize, topology: Topology) -> Self {
+        assert!(width > 0 && height > 0, "boards need at least one tile");
+        assert!(
+            width.checked_mul(height).is_some_and(|cells| cells <= MAX_CELLS),
+            "boards can't have more than {MAX_CELLS} tiles"
+        );
+
+        let mut all = Bitboard::EMPTY;
+        let mut first_column = Bitboard::EMPTY;
+        let mut last_column = Bitboard::EMPTY;
+        let mut first_row = Bitboard::EMPTY;
+        let mut last_row = Bitboard::EMPTY;
+
+        for y in 0..height {
+            for x in 0..width {
+                all.set(y * width + x);
+            }
+            first_column.set(y * width);
+            last_column.set(y * width + width - 1);
+        }
+
+        for x in 0..width {
+            first_row.set(x);
+            last_row.set((height - 1) * width + x);
+        }
+
+        Layout {
+            width,
+            height,
+            topology,
+            all,
+            first_column,
+            last_column,
+            first_row,
+            last_row,
+        }
+    }
+
+    // Where a position really is on the board, which on a torus means wrapping it around the
+    // edges. Returns None if it's off the board.
+    pub fn wrap(&self, pos: Vector) -> Option<Vector> {
+        let (width, height) = (self.width as isize, self.height as isize);
+
+        match self.topology {
+            Topology::Square | Topology::Hex => {
+                ((0..width).contains(&pos.x) && (0..height).contains(&pos.y)).then_some(pos)
+            }
+            Topology::Torus => (width > 0 && height > 0).then(|| Vector {
+                x: pos.x.rem_euclid(width),
+                y: pos.y.rem_euclid(height),
+            }),
+        }
+    }
+
+    // The bit index of a position (after wrapping it on a torus), or None if it's off the board
+    pub fn index(&self, pos: Vector) -> Option<usize> {
+        let pos = self.wrap(pos)?;
+        Some(pos.y as usize * self.width + pos.x as usize)
+    }
+
+    pub fn position(&self, index: usize) -> Vector {
+        Vector {
+            x: (index % self.width) as isize,
+            y: (index / self.width) as isize,
+        }
+    }
+
+    // Move every tile one step in a direction (each part of the step must be -1, 0 or 1),
+    // dropping tiles that would go off the board, or bringing them round to the other side on a
+    // torus
+    pub fn step(&self, bb: &Bitboard, step: Vector) -> Bitboard {
+        if self.topology == Topology::Torus {
+            return self.step_rows(&self.step_columns(bb, step.x), step.y);
+        }
+
+        // Moving sideways off one edge of a row would otherwise land on the other edge of the
+        // next row, so get rid of those tiles first
+        let edge = match step.x {
+            1 => bb.and_not(&self.last_column),
+            -1 => bb.and_not(&self.first_column),
+            _ => bb.clone(),
+        };
+        let bb = &edge;
+
+        let offset = step.y * self.width as isize + step.x;
+
+        let shifted = if offset >= 0 {
+            bb.shift_up(offset as usize)
+        } else {
+            bb.shift_down(offset.unsigned_abs())
+        };
+
+        shifted & &self.all
+    }
+
+    // Move every tile sideways by one column on a torus, with the edge column going round to
+    // the other side
+    fn step_columns(&self, bb: &Bitboard, dx: isize) -> Bitboard {
+        let around = self.width - 1;
+        match dx {
+            1 => {
+                bb.and_not(&self.last_column).shift_up(1)
+                    | (bb.clone() & &self.last_column).shift_down(around)
+            }
+            -1 => {
+                bb.and_not(&self.first_column).shift_down(1)
+                    | (bb.clone() & &self.first_column).shift_up(around)
+            }
+            _ => bb.clone(),
+        }
+    }
+
+    // Move every tile up or down by one row on a torus, with the edge row going round to the
+    // other side
+    fn step_rows(&self, bb: &Bitboard, dy: isize) -> Bitboard {
+        let around = (self.height - 1) * self.width;
+        match dy {
+            1 => {
+                bb.and_not(&self.last_row).shift_up(self.width)
+                    | (bb.clone() & &self.last_row).shift_down(around)
+            }
+            -1 => {
+                bb.and_not(&self.first_row).shift_down(self.width)
+                    | (bb.clone() & &self.first_row).shift_up(around)
+            }
+            _ => bb.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bits(indices: &[usize]) -> Bitboard {
+        let mut bb = Bitboard::EMPTY;
+        for &index in indices {
+            bb.set(index);
+        }
+        bb
+    }
+
+    fn indices(bb: &Bitboard) -> Vec<usize> {
+        bb.iter().collect()
+    }
+
+    const UP: Vector = Vector { x: 0, y: -1 };
+    const DOWN: Vector = Vector { x: 0, y: 1 };
+    const LEFT: Vector = Vector { x: -1, y: 0 };
+    const RIGHT: Vector = Vector { x: 1, y: 0 };
+
+    #[test]
+    fn shifts_across_words() {
+        let bb = bits(&[0, 63, 64, 127]);
+        assert_eq!(indices(&bb.shift_up(1)), [1, 64, 65, 128]);
+        assert_eq!(indices(&bb.shift_up(64)), [64, 127, 128, 191]);
+        assert_eq!(indices(&bb.shift_up(65)), [65, 128, 129, 192]);
+        assert_eq!(indices(&bb.shift_down(1)), [62, 63, 126]);
+        assert_eq!(indices(&bb.shift_down(64)), [0, 63]);
+
+        // Past the last word they're dropped
+        let top = bits(&[MAX_CELLS - 1, 500]);
+        assert_eq!(indices(&top.shift_up(1)), [501]);
+        assert_eq!(indices(&top.shift_down(500)), [0, MAX_CELLS - 501]);
+        assert!(top.shift_down(MAX_CELLS).is_empty());
+        assert!(top.shift_up(MAX_CELLS).is_empty());
+    }
+
+    #[test]
+    fn stored_the_same_however_made() {
+        // Bits that used to be past the first 128 don't make it compare differently
+        let mut bb = bits(&[3, 700]);
+        bb.clear(700);
+        assert_eq!(bb, Bitboard::single(3));
+        assert_eq!(bits(&[200]).shift_down(100), Bitboard::single(100));
+        assert_eq!(bits(&[100, 300]) & bits(&[100]), Bitboard::single(100));
+        assert!(bits(&[100, 300]).intersects(&bits(&[300])));
+        assert!(!bits(&[100]).intersects(&bits(&[300])));
+    }
+
+    #[test]
+    fn steps_drop_tiles_off_the_edges() {
+        let layout = Layout::new(3, 3, Topology::Square);
+        // The corners and the middle
+        let bb = bits(&[0, 2, 4, 6, 8]);
+        assert_eq!(indices(&layout.step(&bb, RIGHT)), [1, 5, 7]);
+        assert_eq!(indices(&layout.step(&bb, LEFT)), [1, 3, 7]);
+        assert_eq!(indices(&layout.step(&bb, UP)), [1, 3, 5]);
+        assert_eq!(indices(&layout.step(&bb, DOWN)), [3, 5, 7]);
+        assert_eq!(indices(&layout.step(&bb, Vector { x: 1, y: 1 })), [4, 8]);
+    }
+
+    #[test]
+    fn steps_wrap_around_on_a_torus() {
+        let layout = Layout::new(3, 3, Topology::Torus);
+        assert_eq!(indices(&layout.step(&bits(&[2]), RIGHT)), [0]);
+        assert_eq!(indices(&layout.step(&bits(&[3]), LEFT)), [5]);
+        assert_eq!(indices(&layout.step(&bits(&[1]), UP)), [7]);
+        assert_eq!(indices(&layout.step(&bits(&[7]), DOWN)), [1]);
+        assert_eq!(indices(&layout.step(&bits(&[0]), Vector { x: -1, y: -1 })), [8]);
+    }
+
+    #[test]
+    fn steps_on_odd_widths() {
+        // 13 doesn't divide 64, so rows start part way through words
+        let layout = Layout::new(13, 13, Topology::Square);
+        let end_of_row_4 = 4 * 13 + 12;
+        assert_eq!(end_of_row_4, 64);
+        assert!(layout.step(&bits(&[end_of_row_4]), RIGHT).is_empty());
+        assert_eq!(indices(&layout.step(&bits(&[end_of_row_4]), DOWN)), [77]);
+        assert_eq!(indices(&layout.step(&bits(&[65]), LEFT)), []);
+        assert_eq!(indices(&layout.step(&bits(&[66]), LEFT)), [65]);
+
+        let layout = Layout::new(13, 13, Topology::Torus);
+        assert_eq!(indices(&layout.step(&bits(&[end_of_row_4]), RIGHT)), [52]);
+        assert_eq!(indices(&layout.step(&bits(&[65]), LEFT)), [77]);
+        assert_eq!(indices(&layout.step(&bits(&[160]), DOWN)), [4]);
+    }
+
+    #[test]
+    fn steps_on_the_biggest_board() {
+        let layout = Layout::new(32, 32, Topology::Square);
+        assert_eq!(layout.all.count(), MAX_CELLS);
+        let last = MAX_CELLS - 1;
+        assert!(layout.step(&bits(&[last]), RIGHT).is_empty());
+        assert!(layout.step(&bits(&[last]), DOWN).is_empty());
+        assert_eq!(indices(&layout.step(&bits(&[last]), UP)), [last - 32]);
+        assert_eq!(layout.step(&layout.all, RIGHT).count(), MAX_CELLS - 32);
+
+        let layout = Layout::new(32, 32, Topology::Torus);
+        assert_eq!(indices(&layout.step(&bits(&[last]), RIGHT)), [last - 31]);
+        assert_eq!(indices(&layout.step(&bits(&[last]), DOWN)), [31]);
+        assert_eq!(indices(&layout.step(&bits(&[0]), UP)), [MAX_CELLS - 32]);
+        assert_eq!(layout.step(&layout.all, Vector { x: 1, y: 1 }), layout.all);
+    }
+}
@@ -0,0 +1,288 @@
+use thiserror::Error;
+
+use crate::{analysis, zobrist, Bitboard, Board, Direction, Rules, ScorchState, Topology, Vector};
+
+// How the walls of a generated map are arranged so that no starting corner is better than another
+#[derive(Clone, Copy, PartialEq, std::fmt::Debug)]
+pub enum Symmetry {
+    // The map looks the same turned upside down, so opposite corners match
+    Rotational,
+    // The map looks the same flipped left to right or top to bottom, so all four corners match
+    Mirror,
+}
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum GenerateError {
+    // The symmetry is about the corners, which are all next to each other on a torus
+    #[error("generated maps can't wrap around")]
+    Torus,
+    // Flipping a hex board left to right turns it into a different shape, though turning it
+    // upside down keeps it the same
+    #[error("hex maps can't be mirrored, only turned upside down")]
+    MirroredHex,
+    // Turning the map upside down only makes opposite corners match, and the corners of a hex
+    // board aren't all the same shape anyway
+    #[error("this kind of map can't be made fair for {0} players")]
+    Unfair(usize),
+}
+
+#[derive(Clone)]
+pub struct GenerateOptions {
+    pub width: usize,
+    pub height: usize,
+    pub players: usize,
+    pub topology: Topology,
+    pub symmetry: Symmetry,
+    // Roughly what fraction of the board should be walls, from 0 to 1
+    pub wall_density: f64,
+    // The rules the map will be played with, so that the walls are checked against the moves
+    // players can actually make
+    pub rules: Rules,
+}
+
+impl Default for GenerateOptions {
+    fn default() -> Self {
+        GenerateOptions {
+            width: crate::DEFAULT_BOARD_SIZE,
+            height: crate::DEFAULT_BOARD_SIZE,
+            players: 2,
+            topology: Topology::Square,
+            symmetry: Symmetry::Rotational,
+            wall_density: 0.15,
+            rules: Rules::default(),
+        }
+    }
+}
+
+// A small random number generator (splitmix64), so the same seed makes the same map everywhere
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(1);
+        zobrist::mix(self.0)
+    }
+
+    // A number from 0 up to but not including n
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+impl Board {
+    // Make a board with walls placed randomly (but symmetrically) from a seed, with the players
+    // starting in the usual places. The same seed and options always make the same board, and
+    // every player can always reach every other player with the rules in the options.
+    pub fn generate(seed: u64, options: &GenerateOptions) -> Result<Board, GenerateError> {
+        match (options.topology, options.symmetry) {
+            (Topology::Torus, _) => return Err(GenerateError::Torus),
+            (Topology::Hex, Symmetry::Mirror) => return Err(GenerateError::MirroredHex),
+            (Topology::Hex, _) | (_, Symmetry::Rotational) if options.players > 2 => {
+                return Err(GenerateError::Unfair(options.players))
+            }
+            _ => {}
+        }
+
+        let mut board = Board::with_topology(
+            options.width,
+            options.height,
+            options.players,
+            options.topology,
+        );
+        board.rules = options.rules.clone();
+        board.seed = Some(seed);
+
+        let mut rng = Rng(seed);
+        let layout = board.layout.clone();
+
+        // Keep the starts and the tiles next to them clear, so nobody starts off boxed in
+        let mut reserved = board.occupied.clone();
+        for dir in Direction::ALL {
+            reserved |= layout.step(&board.occupied, dir.to_vector());
+        }
+
+        // Go through the tiles in a random order, putting walls on each one and its mirror images
+        // unless that would cut the players off from each other
+        let mut order: Vec<usize> = (0..options.width * options.height).collect();
+        for i in (1..order.len()).rev() {
+            order.swap(i, rng.below(i + 1));
+        }
+
+        let target = (order.len() as f64 * options.wall_density.clamp(0.0, 1.0)) as usize;
+
+        for index in order {
+            if board.walls.count() >= target {
+                break;
+            }
+
+            let images = board.symmetric_images(layout.position(index), options.symmetry);
+            let free = images.iter().all(|&pos| {
+                layout
+                    .index(pos)
+                    .is_some_and(|i| !reserved.get(i) && !board.walls.get(i))
+            });
+            if !free {
+                continue;
+            }
+
+            for &pos in &images {
+                board.set_scorch_state(pos, ScorchState::Wall);
+            }
+
+            if !board.starts_connected() {
+                for &pos in &images {
+                    board.set_scorch_state(pos, ScorchState::Empty);
+                }
+            }
+        }
+
+        // Fill in any pockets nobody can get to, since they'd just be wasted space
+        let mut usable = Bitboard::EMPTY;
+        for id in 0..board.players.len() {
+            usable |= analysis::usable_area(&board, id);
+        }
+        for index in layout.all.and_not(&usable).and_not(&board.walls).iter() {
+            board.set_scorch_state(layout.position(index), ScorchState::Wall);
+        }
+
+        Ok(board)
+    }
+
+    // A position and the positions it matches up with under the symmetry
+    fn symmetric_images(&self, pos: Vector, symmetry: Symmetry) -> Vec<Vector> {
+        let (right, bottom) = (self.width as isize - 1, self.height as isize - 1);
+        let mut images = vec![
+            pos,
+            Vector {
+                x: right - pos.x,
+                y: bottom - pos.y,
+            },
+        ];
+
+        if symmetry == Symmetry::Mirror {
+            images.push(Vector {
+                x: right - pos.x,
+                y: pos.y,
+            });
+            images.push(Vector {
+                x: pos.x,
+                y: bottom - pos.y,
+            });
+        }
+
+        images
+    }
+
+    // Whether the first player could get to everyone else's start with the moves the rules allow
+    fn starts_connected(&self) -> bool {
+        let area = analysis::reachable_area(self, 0);
+        self.players
+            .iter()
+            .all(|player| self.layout.index(player.pos).is_some_and(|i| area.get(i)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(players: usize, topology: Topology, symmetry: Symmetry) -> GenerateOptions {
+        GenerateOptions {
+            players,
+            topology,
+            symmetry,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn same_seed_same_map() {
+        let options = GenerateOptions::default();
+        let board = Board::generate(7, &options).unwrap();
+        assert!(board == Board::generate(7, &options).unwrap());
+        assert!(board.walls != Board::generate(8, &options).unwrap().walls);
+        assert_eq!(board.seed, Some(7));
+    }
+
+    #[test]
+    fn walls_are_symmetric() {
+        for options in [
+            options(2, Topology::Square, Symmetry::Rotational),
+            options(2, Topology::Square, Symmetry::Mirror),
+            options(4, Topology::Square, Symmetry::Mirror),
+            options(5, Topology::Square, Symmetry::Mirror),
+            options(2, Topology::Hex, Symmetry::Rotational),
+        ] {
+            for seed in 0..20 {
+                let board = Board::generate(seed, &options).unwrap();
+                assert!(!board.walls.is_empty());
+                for index in board.walls.iter() {
+                    let pos = board.layout.position(index);
+                    for image in board.symmetric_images(pos, options.symmetry) {
+                        assert!(board.walls.get(board.layout.index(image).unwrap()));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn everyone_can_reach_everyone() {
+        let rules = [
+            Rules::default(),
+            Rules {
+                move_lengths: vec![2],
+                ..Default::default()
+            },
+            Rules {
+                move_lengths: vec![2, 3],
+                jumping: true,
+                ..Default::default()
+            },
+            Rules {
+                move_lengths: vec![1],
+                diagonal_moves: true,
+                ..Default::default()
+            },
+        ];
+
+        for rules in rules {
+            for seed in 0..20 {
+                let options = GenerateOptions {
+                    players: 4,
+                    symmetry: Symmetry::Mirror,
+                    wall_density: 0.3,
+                    rules: rules.clone(),
+                    ..Default::default()
+                };
+                let board = Board::generate(seed, &options).unwrap();
+                let area = analysis::reachable_area(&board, 0);
+                for player in board.players() {
+                    assert!(area.get(board.layout.index(player.pos).unwrap()));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn unfair_maps() {
+        let generate = |options| Board::generate(0, &options).map(|_| ());
+        assert_eq!(
+            generate(options(2, Topology::Torus, Symmetry::Rotational)),
+            Err(GenerateError::Torus)
+        );
+        assert_eq!(
+            generate(options(2, Topology::Hex, Symmetry::Mirror)),
+            Err(GenerateError::MirroredHex)
+        );
+        assert_eq!(
+            generate(options(3, Topology::Square, Symmetry::Rotational)),
+            Err(GenerateError::Unfair(3))
+        );
+        assert_eq!(
+            generate(options(4, Topology::Hex, Symmetry::Rotational)),
+            Err(GenerateError::Unfair(4))
+        );
+        assert_eq!(generate(options(3, Topology::Square, Symmetry::Mirror)), Ok(()));
+    }
+}
@@ -1,18 +1,41 @@
-use derive_more::{Add, AddAssign, Mul};
+use std::ops::{Deref, DerefMut};
+
+use derive_more::{Add, AddAssign, Display, Mul};
 use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+pub mod analysis;
+mod bitboard;
+mod generate;
+mod map;
+mod notation;
+mod record;
+mod simultaneous;
+mod view;
+mod zobrist;
 
-pub const BOARD_SIZE: usize = 11;
+pub use bitboard::{Bitboard, Layout, MAX_CELLS};
+pub use generate::{GenerateError, GenerateOptions, Symmetry};
+pub use map::MapError;
+pub use notation::NotationError;
+pub use record::{GameRecord, RecordError};
+pub use view::PlayerView;
+
+pub const DEFAULT_BOARD_SIZE: usize = 11;
 
 #[derive(Clone, Copy, Serialize, Deserialize, PartialEq)]
 pub enum ScorchState {
     Empty,
     Scorched,
+    // Part of the map that can never be moved onto or over
+    Wall,
 }
 
 #[derive(Clone, Copy)]
 pub enum TileContents {
     Empty,
     Scorched,
+    Wall,
     Player(PlayerColor),
 }
 
@@ -21,25 +44,30 @@ impl From<ScorchState> for TileContents {
         match value {
             ScorchState::Empty => Self::Empty,
             ScorchState::Scorched => Self::Scorched,
+            ScorchState::Wall => Self::Wall,
         }
     }
 }
 
-#[derive(Clone, Copy, Add, AddAssign, Mul, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Copy, Add, AddAssign, Mul, PartialEq, Serialize, Deserialize, std::fmt::Debug)]
 pub struct Vector {
     pub x: isize,
     pub y: isize,
 }
 
-#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, std::fmt::Debug)]
 pub enum Direction {
     Up,
     Down,
     Left,
     Right,
+    UpLeft,
+    UpRight,
+    DownLeft,
+    DownRight,
 }
 
-#[derive(Clone, Copy, Serialize, Deserialize)]
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, std::fmt::Debug)]
 pub struct Move {
     pub dir: Direction,
     pub len: usize,
@@ -61,12 +89,45 @@ impl Move {
 }
 
 impl Direction {
+    pub const CARDINAL: [Direction; 4] = [
+        Direction::Up,
+        Direction::Left,
+        Direction::Down,
+        Direction::Right,
+    ];
+
+    pub const ALL: [Direction; 8] = [
+        Direction::Up,
+        Direction::Left,
+        Direction::Down,
+        Direction::Right,
+        Direction::UpLeft,
+        Direction::UpRight,
+        Direction::DownLeft,
+        Direction::DownRight,
+    ];
+
+    // The six directions on a hex board, where each row is shifted half a tile right of the row
+    // above (see Topology::Hex)
+    pub const HEX: [Direction; 6] = [
+        Direction::Up,
+        Direction::UpRight,
+        Direction::Right,
+        Direction::Down,
+        Direction::DownLeft,
+        Direction::Left,
+    ];
+
     pub fn to_vector(&self) -> Vector {
         match self {
             Direction::Up => Vector { x: 0, y: -1 },
             Direction::Down => Vector { x: 0, y: 1 },
             Direction::Left => Vector { x: -1, y: 0 },
             Direction::Right => Vector { x: 1, y: 0 },
+            Direction::UpLeft => Vector { x: -1, y: -1 },
+            Direction::UpRight => Vector { x: 1, y: -1 },
+            Direction::DownLeft => Vector { x: -1, y: 1 },
+            Direction::DownRight => Vector { x: 1, y: 1 },
         }
     }
 
@@ -76,6 +137,134 @@ impl Direction {
             Direction::Down => Direction::Up,
             Direction::Left => Direction::Right,
             Direction::Right => Direction::Left,
+            Direction::UpLeft => Direction::DownRight,
+            Direction::UpRight => Direction::DownLeft,
+            Direction::DownLeft => Direction::UpRight,
+            Direction::DownRight => Direction::UpLeft,
+        }
+    }
+
+    pub fn is_diagonal(&self) -> bool {
+        !Direction::CARDINAL.contains(self)
+    }
+}
+
+// Which tiles get scorched when a player moves
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ScorchRule {
+    // The tile the player left and every tile they passed over
+    Path,
+    // Only the tile the player left, so longer moves jump over the tiles in between
+    Origin,
+}
+
+// The shape of the board, which decides which tiles are next to each other
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, Default, std::fmt::Debug)]
+pub enum Topology {
+    // Square tiles, and moves can't go past the edges
+    #[default]
+    Square,
+    // Square tiles, but the edges wrap around, so going off the left edge comes back on the right
+    // edge and going off the top comes back at the bottom
+    Torus,
+    // Hexagonal tiles, using axial coordinates: the tiles are stored in rows like a square board,
+    // but each row is drawn shifted half a tile right of the row above, making the board a
+    // rhombus. The tiles Up and Down from a tile are drawn up-left and down-right of it, and
+    // together with Left, Right, UpRight and DownLeft they make its six neighbours.
+    Hex,
+}
+
+impl Topology {
+    // The directions players can move in
+    pub fn directions(&self, diagonal_moves: bool) -> &'static [Direction] {
+        match self {
+            Topology::Hex => &Direction::HEX,
+            Topology::Square | Topology::Torus if diagonal_moves => &Direction::ALL,
+            Topology::Square | Topology::Torus => &Direction::CARDINAL,
+        }
+    }
+
+    // The direction a tile is in from another one based on where it's drawn on the screen. This
+    // is the same as the direction on the board except on a hex board, where the neighbours
+    // drawn up-left and down-right are Up and Down.
+    pub fn from_screen(&self, dir: Direction) -> Direction {
+        match (self, dir) {
+            (Topology::Hex, Direction::UpLeft) => Direction::Up,
+            (Topology::Hex, Direction::DownRight) => Direction::Down,
+            _ => dir,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct Rules {
+    // How many tiles a player is allowed to move in one turn
+    pub move_lengths: Vec<usize>,
+    pub diagonal_moves: bool,
+    // Whether longer moves can pass over scorched tiles and other players
+    pub jumping: bool,
+    // Whether it's a draw when a move leaves every remaining player stuck at once, instead of a
+    // win for the player who moved
+    pub mutual_blockade_draw: bool,
+    // Whether moving onto another player eliminates them. If not, moving onto them isn't allowed.
+    pub capture: bool,
+    pub scorch: ScorchRule,
+    // How many tiles away players can see with fog of war, or None if everyone can see the whole
+    // board
+    pub fog: Option<usize>,
+    // Whether the game ends as soon as nobody can reach an opponent, with the side that has the
+    // most room left winning
+    pub territory: bool,
+    // Whether everyone moves at once each round instead of taking turns (see simultaneous.rs)
+    pub simultaneous: bool,
+}
+
+impl Default for Rules {
+    // The original rules: move 1 or 2 tiles in a cardinal direction and scorch the whole path
+    fn default() -> Self {
+        Rules {
+            move_lengths: vec![1, 2],
+            diagonal_moves: false,
+            jumping: false,
+            mutual_blockade_draw: false,
+            capture: true,
+            scorch: ScorchRule::Path,
+            fog: None,
+            territory: false,
+            simultaneous: false,
+        }
+    }
+}
+
+impl Rules {
+    pub fn min_move_len(&self) -> usize {
+        self.move_lengths.iter().copied().min().unwrap_or(1)
+    }
+
+    pub fn max_move_len(&self) -> usize {
+        self.move_lengths.iter().copied().max().unwrap_or(1)
+    }
+
+    // Update a move being previewed when a direction is pressed. Pressing the same direction
+    // again makes the move longer and pressing the opposite direction makes it shorter, until it
+    // can't get any shorter and switches direction instead.
+    pub fn preview_move(&self, current: Option<Move>, dir: Direction) -> Move {
+        let longer = |len| self.move_lengths.iter().copied().filter(|&l| l > len).min();
+        let shorter = |len| self.move_lengths.iter().copied().filter(|&l| l < len).max();
+
+        match current {
+            Some(old) if dir == old.dir => Move {
+                dir,
+                len: longer(old.len).unwrap_or(old.len),
+            },
+            Some(old) if dir == old.dir.opposite() && shorter(old.len).is_some() => Move {
+                dir: old.dir,
+                len: shorter(old.len).unwrap_or(old.len),
+            },
+            _ => Move {
+                dir,
+                len: self.min_move_len(),
+            },
         }
     }
 }
@@ -89,173 +278,1468 @@ pub enum PlayerColor {
     Magenta,
 }
 
+impl PlayerColor {
+    // The colors given to players in the order they join a game
+    pub const ALL: [PlayerColor; MAX_PLAYERS] = [
+        PlayerColor::Green,
+        PlayerColor::Yellow,
+        PlayerColor::Blue,
+        PlayerColor::Magenta,
+        PlayerColor::Cyan,
+    ];
+}
+
+pub const MAX_PLAYERS: usize = 5;
+
+// Players are never removed from the board (eliminated ones are just marked), so a player's index
+// in the players list is stable for the whole game and can be used as its ID
+pub type PlayerId = usize;
+
 #[derive(Clone, Serialize, Deserialize, PartialEq)]
 pub struct Player {
     pub pos: Vector,
     pub color: PlayerColor,
+    // Which team the player is on, or None if they're playing for themselves. Teammates can't
+    // move onto or over each other, and a team wins together once every other team is out.
+    pub team: Option<usize>,
+}
+
+#[derive(Error, Debug, Clone, Copy, PartialEq)]
+pub enum MoveError {
+    #[error("it isn't player {0}'s turn")]
+    WrongTurn(PlayerId),
+    #[error("there is no player {0}")]
+    UnknownPlayer(PlayerId),
+    #[error("player {0} has already been eliminated")]
+    Eliminated(PlayerId),
+    #[error("the game is already over")]
+    GameOver,
+    #[error("move isn't allowed by the rules")]
+    NotAllowed,
+    #[error("move goes off the board")]
+    OutOfBounds,
+    #[error("path is blocked")]
+    BlockedPath,
+    #[error("expected {expected} moves but got {found}")]
+    WrongMoveCount { expected: usize, found: usize },
+    #[error("everyone moves at once, so moves can't be made one at a time")]
+    Simultaneous,
+}
+
+// Why a player was knocked out of the game. Displays as what happened to them, e.g. "was captured".
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Display, std::fmt::Debug)]
+pub enum LossReason {
+    // Another player moved onto them
+    #[display(fmt = "was captured")]
+    Captured,
+    // Another player moved over them, scorching their tile
+    #[display(fmt = "was run over")]
+    RunOver,
+    // Every neighbouring tile is scorched or a wall
+    #[display(fmt = "was surrounded")]
+    Surrounded,
+    // Not surrounded, but still nowhere to go (usually because of other players)
+    #[display(fmt = "had no legal moves")]
+    NoLegalMoves,
+    #[display(fmt = "went off the board")]
+    OffBoard,
+    #[display(fmt = "resigned")]
+    Resigned,
+    #[display(fmt = "ran out of time")]
+    Timeout,
+    // Moved onto the same tile as another player at the same time (only with simultaneous moves)
+    #[display(fmt = "crashed head-on")]
+    Collided,
+    // Had less room than another side once nobody could reach each other (only with the
+    // territory rule)
+    #[display(fmt = "had less room")]
+    LessRoom,
+}
+
+impl LossReason {
+    // Losses from being stuck rather than from something another player did directly
+    pub fn is_blockade(&self) -> bool {
+        matches!(self, LossReason::Surrounded | LossReason::NoLegalMoves)
+    }
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, std::fmt::Debug)]
+pub enum GameOutcome {
+    Ongoing,
+    // The reason is why the last other player was eliminated
+    Win { winner: PlayerId, reason: LossReason },
+    // Everyone left is on the same team, so the whole team wins (including teammates that were
+    // eliminated). The reason is why the last other player was eliminated.
+    TeamWin { team: usize, reason: LossReason },
+    Draw { reason: DrawReason },
+}
+
+// Why a game ended without a winner. Displays as a description of the draw, e.g. "draw by mutual
+// blockade".
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Display, std::fmt::Debug)]
+pub enum DrawReason {
+    // Every remaining player got stuck on the same move (only with the mutual blockade rule)
+    #[display(fmt = "draw by mutual blockade")]
+    MutualBlockade,
+    // Everyone was knocked out by the same round of moves (only with simultaneous moves)
+    #[display(fmt = "draw, since everyone lost at once")]
+    EveryoneLost,
+    // More than one side had the most room (only with the territory rule)
+    #[display(fmt = "draw, with the same room on each side")]
+    EqualRoom,
 }
 
 pub struct TurnResult {
-    pub winner: Option<PlayerColor>,
+    pub outcome: GameOutcome,
+    // Players that were eliminated by this move, in the order they were eliminated
+    pub eliminated: Vec<(PlayerId, LossReason)>,
     pub changes: Vec<(Vector, TileContents)>,
+    // How much room each player had, if the territory rule ended the game (see
+    // analysis::territory)
+    pub territory: Option<Vec<usize>>,
+    // Pass this to Board::unmake_move to take the move back
+    pub undo: Undo,
+}
+
+// Everything needed to put the board back how it was before a move
+#[derive(Clone, Serialize, Deserialize, PartialEq, std::fmt::Debug)]
+pub struct Undo {
+    player: PlayerId,
+    from: Vector,
+    // Everyone else who moved at the same time and where from, for simultaneous moves
+    also_moved: Vec<(PlayerId, Vector)>,
+    scorched: Vec<Vector>,
+    turn: PlayerId,
+    eliminated_len: usize,
+    outcome: GameOutcome,
+    hash: u64,
 }
 
+// Stored as bitboards so tiles can be checked quickly, but serialized the same way as when it
+// was an array of cells
 #[derive(Clone, Serialize, Deserialize, PartialEq)]
+#[serde(try_from = "BoardRepr", into = "BoardRepr")]
 pub struct Board {
-    pub cells: [[ScorchState; BOARD_SIZE]; BOARD_SIZE],
-    pub players: Vec<Player>,
-    pub turn: usize,
+    width: usize,
+    height: usize,
+    layout: Layout,
+    scorched: Bitboard,
+    walls: Bitboard,
+    // Where the players still in the game are, kept in sync with their positions
+    occupied: Bitboard,
+    // Only changed through Board's methods, so the hash and occupied tiles stay up to date
+    pub(crate) players: Vec<Player>,
+    pub rules: Rules,
+    // Players that have lost and why, in the order they were eliminated
+    pub(crate) eliminated: Vec<(PlayerId, LossReason)>,
+    // Use set_turn to change this, so the hash stays up to date
+    pub(crate) turn: PlayerId,
+    pub outcome: GameOutcome,
+    // The seed the map was made from, if it was generated, so it can be shown to players
+    pub seed: Option<u64>,
+    // Players out of sight, when this is what one player can see of the board (see view.rs).
+    // Where they are isn't known, so they don't take up a tile, aren't part of the hash, and
+    // never count as having lost.
+    hidden: Vec<PlayerId>,
+    // Zobrist hash of the scorched tiles, player positions and whose turn it is, updated as
+    // moves are made
+    hash: u64,
 }
 
 impl Board {
+    // Make an empty board of the given size with a player in opposite corners
+    pub fn new(width: usize, height: usize) -> Self {
+        Self::with_players(width, height, 2)
+    }
+
+    // Make an empty board of the given size for a free-for-all between player_count players.
+    // The first four start in the corners and the fifth starts in the middle.
+    pub fn with_players(width: usize, height: usize, player_count: usize) -> Self {
+        Self::with_topology(width, height, player_count, Topology::Square)
+    }
+
+    // Make an empty board with the given topology. On a torus the corners are all next to each
+    // other, so the players start spread out across the board instead: the first two in
+    // opposite quarters, then the other two quarters, then the fifth in between. Hex boards use
+    // the corners like square boards, where the first two are the furthest apart.
+    pub fn with_topology(
+        width: usize,
+        height: usize,
+        player_count: usize,
+        topology: Topology,
+    ) -> Self {
+        assert!(
+            (2..=MAX_PLAYERS).contains(&player_count),
+            "games need between 2 and {MAX_PLAYERS} players"
+        );
+
+        assert!(
+            Self::has_room_for(width, height, player_count, topology),
+            "a {width}x{height} board doesn't have a separate start for {player_count} players"
+        );
+
+        let mut board = Self::blank(width, height, topology);
+        board.players = Self::start_positions(width, height, topology)
+            .into_iter()
+            .zip(PlayerColor::ALL)
+            .take(player_count)
+            .map(|(pos, color)| Player {
+                pos,
+                color,
+                team: None,
+            })
+            .collect();
+        board.update_occupied();
+        board.hash = board.compute_hash();
+        board
+    }
+
+    // A board with nobody on it yet, for maps and notation to put their own players on
+    pub(crate) fn blank(width: usize, height: usize, topology: Topology) -> Self {
+        Board {
+            width,
+            height,
+            layout: Layout::new(width, height, topology),
+            scorched: Bitboard::EMPTY,
+            walls: Bitboard::EMPTY,
+            occupied: Bitboard::EMPTY,
+            players: Vec::new(),
+            rules: Rules::default(),
+            eliminated: Vec::new(),
+            turn: 0,
+            outcome: GameOutcome::Ongoing,
+            seed: None,
+            hidden: Vec::new(),
+            hash: 0,
+        }
+    }
+
+    // Where players start on an empty board, in player order (see with_topology)
+    fn start_positions(width: usize, height: usize, topology: Topology) -> [Vector; MAX_PLAYERS] {
+        let (right, bottom) = (width as isize - 1, height as isize - 1);
+        match topology {
+            Topology::Square | Topology::Hex => [
+                Vector { x: 0, y: 0 },
+                Vector { x: right, y: bottom },
+                Vector { x: right, y: 0 },
+                Vector { x: 0, y: bottom },
+                Vector {
+                    x: right / 2,
+                    y: bottom / 2,
+                },
+            ],
+            Topology::Torus => {
+                let (half_x, half_y) = (width as isize / 2, height as isize / 2);
+                [
+                    Vector { x: 0, y: 0 },
+                    Vector {
+                        x: half_x,
+                        y: half_y,
+                    },
+                    Vector { x: half_x, y: 0 },
+                    Vector { x: 0, y: half_y },
+                    Vector {
+                        x: half_x / 2,
+                        y: half_y / 2,
+                    },
+                ]
+            }
+        }
+    }
+
+    // Whether an empty board is big enough for every player to start on a different tile. Tiny
+    // boards put the fifth player in the middle on top of someone in a corner.
+    pub fn has_room_for(
+        width: usize,
+        height: usize,
+        player_count: usize,
+        topology: Topology,
+    ) -> bool {
+        let starts = Self::start_positions(width, height, topology);
+        let starts = &starts[..player_count.min(MAX_PLAYERS)];
+        width > 0
+            && height > 0
+            && starts
+                .iter()
+                .enumerate()
+                .all(|(i, start)| !starts[..i].contains(start))
+    }
+
+    // Check that the board makes sense, since boards can come from the other player over the
+    // network. The cells always match the dimensions since that's checked when deserializing.
+    pub fn is_consistent(&self) -> bool {
+        let positions: Vec<Vector> =
+            self.players_in_sight().map(|id| self.players[id].pos).collect();
+
+        (2..=MAX_PLAYERS).contains(&self.players.len())
+            && self.turn < self.players.len()
+            && (self.outcome != GameOutcome::Ongoing || !self.is_eliminated(self.turn))
+            && self.eliminated.iter().all(|&(id, _)| id < self.players.len())
+            && self.hidden.iter().all(|&id| id < self.players.len())
+            && self
+                .eliminated
+                .iter()
+                .enumerate()
+                .all(|(i, &(id, _))| self.eliminated[..i].iter().all(|&(other, _)| other != id))
+            // Captured players stay on the tile they were captured on, under whoever caught them,
+            // and players out of sight are all put on the same tile, so only players still in the
+            // game and in sight need their own tile
+            && self.players.iter().all(|player| {
+                self.scorch_state(player.pos)
+                    .is_some_and(|state| state != ScorchState::Wall)
+            })
+            && positions
+                .iter()
+                .enumerate()
+                .all(|(i, pos)| !positions[..i].contains(pos))
+            && match self.outcome {
+                GameOutcome::Win { winner, .. } => winner < self.players.len(),
+                GameOutcome::TeamWin { team, .. } => self.team_color(team).is_some(),
+                GameOutcome::Draw { .. } | GameOutcome::Ongoing => true,
+            }
+            && !self.rules.move_lengths.is_empty()
+            && self
+                .rules
+                .move_lengths
+                .iter()
+                .all(|&len| len > 0 && len <= self.width.max(self.height))
+    }
+
+    // Check if a position is on the board
+    pub fn contains(&self, pos: Vector) -> bool {
+        self.layout.index(pos).is_some()
+    }
+
+    pub fn layout(&self) -> &Layout {
+        &self.layout
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn topology(&self) -> Topology {
+        self.layout.topology
+    }
+
+    // Change what happens at the edges of the board. The players stay where they are, so this is
+    // for boards whose starting places were already chosen, like maps.
+    pub fn set_topology(&mut self, topology: Topology) {
+        self.layout = Layout::new(self.width, self.height, topology);
+    }
+
+    // The directions players can move in, which depends on the rules and the topology
+    pub fn directions(&self) -> &'static [Direction] {
+        self.topology().directions(self.rules.diagonal_moves)
+    }
+
+    // Check if the move has an allowed direction and length, regardless of where it goes
+    pub fn allows(&self, attempted_move: Move) -> bool {
+        self.rules.move_lengths.contains(&attempted_move.len)
+            && self.directions().contains(&attempted_move.dir)
+    }
+
+    // The position an offset away from another one, wrapping around the edges on a torus, or
+    // None if it's off the board
+    pub fn translate(&self, pos: Vector, offset: Vector) -> Option<Vector> {
+        self.layout.wrap(pos + offset)
+    }
+
+    // A hash of the position, which is the same for boards with the same scorched tiles, players
+    // in the same places and the same player to move. Much quicker than comparing whole boards.
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
+
+    // Work out the hash from scratch, rather than updating it as things change
+    fn compute_hash(&self) -> u64 {
+        let mut hash = zobrist::turn(self.turn);
+
+        for index in self.scorched.iter() {
+            hash ^= zobrist::scorched(index);
+        }
+
+        for index in self.walls.iter() {
+            hash ^= zobrist::wall(index);
+        }
+
+        for id in self.players_in_sight() {
+            if let Some(index) = self.layout.index(self.players[id].pos) {
+                hash ^= zobrist::player(id, index);
+            }
+        }
+
+        hash
+    }
+
+    // The hash key for a player standing where they are now, if they're on the board and in sight
+    fn player_key(&self, player: PlayerId) -> u64 {
+        if self.hidden.contains(&player) {
+            return 0;
+        }
+        self.layout
+            .index(self.players[player].pos)
+            .map_or(0, |index| zobrist::player(player, index))
+    }
+
+    pub fn players(&self) -> &[Player] {
+        &self.players
+    }
+
+    // Players that have lost and why, in the order they were eliminated
+    pub fn eliminated(&self) -> &[(PlayerId, LossReason)] {
+        &self.eliminated
+    }
+
+    // The player whose turn it is
+    pub fn turn(&self) -> PlayerId {
+        self.turn
+    }
+
+    pub fn set_turn(&mut self, turn: PlayerId) {
+        self.hash ^= zobrist::turn(self.turn) ^ zobrist::turn(turn);
+        self.turn = turn;
+    }
+
+    // Every scorched tile
+    pub fn scorched(&self) -> Bitboard {
+        self.scorched.clone()
+    }
+
+    pub fn walls(&self) -> Bitboard {
+        self.walls.clone()
+    }
+
+    // Every tile that can't be moved onto, whether it's scorched or a wall
+    pub fn blocked(&self) -> Bitboard {
+        self.scorched.clone() | &self.walls
+    }
+
+    // Every tile with a player that's still in the game on it
+    pub fn occupied(&self) -> Bitboard {
+        self.occupied.clone()
+    }
+
+    pub fn is_occupied(&self, pos: Vector) -> bool {
+        self.layout
+            .index(pos)
+            .is_some_and(|index| self.occupied.get(index))
+    }
+
+    fn update_occupied(&mut self) {
+        let mut occupied = Bitboard::EMPTY;
+        for id in self.players_in_sight() {
+            if let Some(index) = self.layout.index(self.players[id].pos) {
+                occupied.set(index);
+            }
+        }
+        self.occupied = occupied;
+    }
+
+    pub fn is_eliminated(&self, player: PlayerId) -> bool {
+        self.eliminated.iter().any(|&(id, _)| id == player)
+    }
+
+    // IDs of all players still in the game
+    pub fn active_players(&self) -> impl Iterator<Item = PlayerId> + '_ {
+        (0..self.players.len()).filter(|&id| !self.is_eliminated(id))
+    }
+
+    // Players still in the game whose positions are known, which is all of them except in a
+    // player's view of the board with fog of war
+    fn players_in_sight(&self) -> impl Iterator<Item = PlayerId> + '_ {
+        self.active_players().filter(|id| !self.hidden.contains(id))
+    }
+
+    // The only player left in the game, if everyone else has been eliminated
+    pub fn last_player_standing(&self) -> Option<PlayerId> {
+        let mut active = self.active_players();
+        match (active.next(), active.next()) {
+            (Some(last), None) => Some(last),
+            _ => None,
+        }
+    }
+
+    // The team everyone still in the game is on, if they're all on the same one
+    pub fn last_team_standing(&self) -> Option<usize> {
+        let mut teams = self.active_players().map(|id| self.players[id].team);
+        let team = teams.next()??;
+        teams.all(|other| other == Some(team)).then_some(team)
+    }
+
+    // How the game ends if it's over now, with reason being why the last player to go out lost
+    fn finished_outcome(&self, reason: LossReason) -> Option<GameOutcome> {
+        if let Some(team) = self.last_team_standing() {
+            Some(GameOutcome::TeamWin { team, reason })
+        } else {
+            self.last_player_standing()
+                .map(|winner| GameOutcome::Win { winner, reason })
+        }
+    }
+
+    // Whether the player won the game, either by themselves or with their team
+    pub fn is_winner(&self, player: PlayerId) -> bool {
+        match self.outcome {
+            GameOutcome::Win { winner, .. } => winner == player,
+            GameOutcome::TeamWin { team, .. } => {
+                self.players.get(player).is_some_and(|p| p.team == Some(team))
+            }
+            GameOutcome::Ongoing | GameOutcome::Draw { .. } => false,
+        }
+    }
+
+    // Whether two different players are on the same team
+    pub fn are_teammates(&self, a: PlayerId, b: PlayerId) -> bool {
+        a != b && self.players[a].team.is_some() && self.players[a].team == self.players[b].team
+    }
+
+    // Split the players into teams by turn order, so the teams take turns: with 4 players and
+    // 2 teams, the first and third players are against the second and fourth
+    pub fn set_teams(&mut self, team_count: usize) {
+        for (id, player) in self.players.iter_mut().enumerate() {
+            player.team = Some(id % team_count);
+        }
+    }
+
+    // The color a team is shown in, which is the color of its first player
+    pub fn team_color(&self, team: usize) -> Option<PlayerColor> {
+        self.players
+            .iter()
+            .find(|player| player.team == Some(team))
+            .map(|player| player.color)
+    }
+
+    // Tiles with a teammate of the player on them, who can't be moved onto or over
+    pub fn teammates(&self, player: PlayerId) -> Bitboard {
+        let mut teammates = Bitboard::EMPTY;
+        for id in self.active_players() {
+            if let (true, Some(index)) = (
+                self.are_teammates(player, id),
+                self.layout.index(self.players[id].pos),
+            ) {
+                teammates.set(index);
+            }
+        }
+        teammates
+    }
+
+    // The next player still in the game after the given one, wrapping around
+    pub fn next_active_player(&self, player: PlayerId) -> PlayerId {
+        (1..=self.players.len())
+            .map(|offset| (player + offset) % self.players.len())
+            .find(|&id| !self.is_eliminated(id))
+            .unwrap_or(player)
+    }
+
     // Get the state of the tile at this position, or return None if there isn't one there
+    pub fn scorch_state(&self, pos: Vector) -> Option<ScorchState> {
+        let index = self.layout.index(pos)?;
+
+        Some(if self.walls.get(index) {
+            ScorchState::Wall
+        } else if self.scorched.get(index) {
+            ScorchState::Scorched
+        } else {
+            ScorchState::Empty
+        })
+    }
+
+    // The old way of getting a tile's state, from when the board was a grid of cells
+    #[deprecated(note = "use scorch_state, which returns the state itself")]
     pub fn scorch_state_at(&self, pos: Vector) -> Option<&ScorchState> {
-        let Ok(x): Result<usize, _> = pos.x.try_into() else { return None };
-        let Ok(y): Result<usize, _> = pos.y.try_into() else { return None };
+        Some(match self.scorch_state(pos)? {
+            ScorchState::Empty => &ScorchState::Empty,
+            ScorchState::Scorched => &ScorchState::Scorched,
+            ScorchState::Wall => &ScorchState::Wall,
+        })
+    }
 
-        self.cells.get(y).map(|row| row.get(x)).flatten()
+    // The old way of changing a tile's state. The tiles aren't stored one by one any more, so
+    // the change is made on the board once the returned value is dropped.
+    #[deprecated(note = "use set_scorch_state")]
+    pub fn scorch_state_at_mut(&mut self, pos: Vector) -> Option<ScorchStateMut<'_>> {
+        let state = self.scorch_state(pos)?;
+        Some(ScorchStateMut {
+            board: self,
+            pos,
+            state,
+        })
     }
 
-    // Same as tile_at but mutable so it can be changed
-    pub fn scorch_state_at_mut(&mut self, pos: Vector) -> Option<&mut ScorchState> {
-        let Ok(x): Result<usize, _> = pos.x.try_into() else { return None };
-        let Ok(y): Result<usize, _> = pos.y.try_into() else { return None };
+    // Change the state of the tile at this position. Returns false if there isn't one there.
+    pub fn set_scorch_state(&mut self, pos: Vector, state: ScorchState) -> bool {
+        let Some(index) = self.layout.index(pos) else { return false };
+
+        if self.scorched.get(index) != (state == ScorchState::Scorched) {
+            self.hash ^= zobrist::scorched(index);
+        }
+        if self.walls.get(index) != (state == ScorchState::Wall) {
+            self.hash ^= zobrist::wall(index);
+        }
 
-        self.cells.get_mut(y).map(|row| row.get_mut(x)).flatten()
+        self.scorched.clear(index);
+        self.walls.clear(index);
+        match state {
+            ScorchState::Empty => {}
+            ScorchState::Scorched => self.scorched.set(index),
+            ScorchState::Wall => self.walls.set(index),
+        }
+        true
     }
 
     pub fn tile_contents_at(&self, pos: Vector) -> Option<TileContents> {
-        for player in &self.players {
-            if player.pos == pos {
-                return Some(TileContents::Player(player.color));
+        let pos = self.layout.wrap(pos)?;
+
+        // Only look for which player it is if there's one there at all
+        if self.is_occupied(pos) {
+            for id in self.players_in_sight() {
+                if self.players[id].pos == pos {
+                    return Some(TileContents::Player(self.players[id].color));
+                }
             }
         }
 
-        self.scorch_state_at(pos)
-            .copied()
+        self.scorch_state(pos)
             .map(|scorch_state| scorch_state.into())
     }
 
-    pub fn is_move_valid(&self, player_index: usize, attempted_move: Move) -> bool {
-        matches!(
-            self.scorch_state_at(self.players[player_index].pos + attempted_move.to_vector()),
-            Some(ScorchState::Empty)
-        )
+    pub fn is_move_valid(&self, player: PlayerId, attempted_move: Move) -> bool {
+        self.check_move(player, attempted_move).is_ok()
+    }
+
+    // Check whether a move is legal, and if not, why
+    pub fn check_move(&self, player: PlayerId, attempted_move: Move) -> Result<(), MoveError> {
+        if player >= self.players.len() {
+            return Err(MoveError::UnknownPlayer(player));
+        }
+
+        if self.is_eliminated(player) {
+            return Err(MoveError::Eliminated(player));
+        }
+
+        if !self.allows(attempted_move) {
+            return Err(MoveError::NotAllowed);
+        }
+
+        let start = self.players[player].pos;
+        let path: Option<Vec<Vector>> = attempted_move
+            .tiles_along_path()
+            .into_iter()
+            .map(|tile| self.translate(start, tile))
+            .collect();
+
+        let Some(path) = path.filter(|_| self.contains(start)) else {
+            return Err(MoveError::OutOfBounds);
+        };
+
+        let occupied = |pos| pos != start && self.is_occupied(pos);
+        let teammates = self.teammates(player);
+
+        for (i, &pos) in path.iter().enumerate() {
+            let is_destination = i == path.len() - 1;
+
+            // Walls and teammates can never be jumped over (or landed on)
+            let teammate = self.layout.index(pos).is_some_and(|index| teammates.get(index));
+            if teammate || self.scorch_state(pos) == Some(ScorchState::Wall) {
+                return Err(MoveError::BlockedPath);
+            }
+
+            // Tiles in the middle of the path only matter if jumping over things isn't allowed,
+            // and you can only land on someone else if capturing them is allowed
+            if is_destination || !self.rules.jumping {
+                let player_in_the_way = occupied(pos) && !(is_destination && self.rules.capture);
+
+                // On a torus a move can come all the way round to where it started, which will
+                // have been scorched by then
+                let scorched =
+                    pos == start || self.scorch_state(pos) != Some(ScorchState::Empty);
+
+                if player_in_the_way || scorched {
+                    return Err(MoveError::BlockedPath);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // Every move the player could legally make right now
+    pub fn legal_moves(&self, player: PlayerId) -> Vec<Move> {
+        let mut moves = Vec::new();
+        self.for_each_legal_move(player, |m| {
+            moves.push(m);
+            true
+        });
+        moves
+    }
+
+    pub fn has_legal_move(&self, player: PlayerId) -> bool {
+        let mut found = false;
+        self.for_each_legal_move(player, |_| {
+            found = true;
+            false
+        });
+        found
+    }
+
+    // Generate legal moves by stepping the player's tile along each direction as a bitboard,
+    // stopping early if the callback returns false. This has to agree with check_move.
+    fn for_each_legal_move(&self, player: PlayerId, mut callback: impl FnMut(Move) -> bool) {
+        if player >= self.players.len() || self.is_eliminated(player) {
+            return;
+        }
+
+        let Some(start) = self.layout.index(self.players[player].pos) else { return };
+        let origin = Bitboard::single(start);
+
+        // Tiles that can be passed over, and tiles that can be landed on. The player's own tile
+        // is left out of both since it gets scorched as they leave, which only matters on a torus
+        // where moves can come back round to it.
+        let passable = self.layout.all.and_not(&self.blocked()).and_not(&self.occupied);
+        let landable = if self.rules.capture {
+            self.layout.all.and_not(&self.blocked()).and_not(&origin)
+        } else {
+            passable.clone()
+        };
+        // Tiles that stop a move even when jumping
+        let stoppers = self.teammates(player) | &self.walls;
+        let landable = landable.and_not(&stoppers);
+
+        let max_len = self.rules.max_move_len();
+
+        for &dir in self.directions() {
+            let step = dir.to_vector();
+            let mut tile = origin.clone();
+
+            for len in 1..=max_len {
+                tile = self.layout.step(&tile, step);
+                if tile.is_empty() {
+                    break;
+                }
+
+                if self.rules.move_lengths.contains(&len)
+                    && tile.intersects(&landable)
+                    && !callback(Move { dir, len })
+                {
+                    return;
+                }
+
+                if (!self.rules.jumping && !tile.intersects(&passable))
+                    || tile.intersects(&stoppers)
+                {
+                    break;
+                }
+            }
+        }
     }
 
     // Move the specified player with the specified move
-    pub fn make_move(&mut self, player_index: usize, attempted_move: Move) -> TurnResult {
+    pub fn make_move(
+        &mut self,
+        player: PlayerId,
+        attempted_move: Move,
+    ) -> Result<TurnResult, MoveError> {
+        if self.outcome != GameOutcome::Ongoing {
+            return Err(MoveError::GameOver);
+        }
+
+        // Use resolve_simultaneous instead
+        if self.rules.simultaneous {
+            return Err(MoveError::Simultaneous);
+        }
+
+        self.check_move(player, attempted_move)?;
+
+        if player != self.turn {
+            return Err(MoveError::WrongTurn(player));
+        }
+
         let mut changes = Vec::new();
+        let mut undo = self.undo_record(player);
 
-        for _ in 0..attempted_move.len {
-            let current_pos = self.players[player_index].pos;
-            let current_cell = self
-                .scorch_state_at_mut(current_pos)
-                .expect("Invalid player position for move");
+        // With the origin rule only the first tile gets scorched and the rest are jumped over
+        let scorched_len = match self.rules.scorch {
+            ScorchRule::Path => attempted_move.len,
+            ScorchRule::Origin => 1,
+        };
 
-            *current_cell = ScorchState::Scorched;
-            changes.push((current_pos, TileContents::Scorched));
-            self.players[player_index].pos += attempted_move.dir.to_vector();
+        self.hash ^= self.player_key(player);
+
+        for i in 0..attempted_move.len {
+            let current_pos = self.players[player].pos;
+
+            // Tiles that were already scorched (from jumping over them) stay scorched on undo
+            if i < scorched_len && self.scorch_state(current_pos) == Some(ScorchState::Empty) {
+                self.set_scorch_state(current_pos, ScorchState::Scorched);
+                changes.push((current_pos, TileContents::Scorched));
+                undo.scorched.push(current_pos);
+            }
+            // check_move made sure every tile along the way is on the board
+            let next = current_pos + attempted_move.dir.to_vector();
+            self.players[player].pos = self.layout.wrap(next).unwrap_or(next);
         }
 
+        self.hash ^= self.player_key(player);
+        self.update_occupied();
+
         changes.push((
-            self.players[player_index].pos,
-            TileContents::Player(self.players[player_index].color),
+            self.players[player].pos,
+            TileContents::Player(self.players[player].color),
         ));
 
-        // Remove players that lost (in reverse order to avoid messing up the indices
-        // during the loop)
-
-        // This has problems because both players can lose at once and when this happens either
-        // player can win based on their order in the list when the current player should always
-        // win in case of a conflict. Reordering the indices to put the current player last would also
-        // cause problems because removing the current player would change the indices of
-        // everything after it, which is why they have to be in reverse order.
-        // This could be fixed by reordering the players list itself or not actually
-        // deleting the players or keeping track of them with something other than indices.
-
-        /*for i in (0..self.players.len()).rev() {
-            if self.player_lost(i) {
-                let removed_position = self.players[i].pos;
-                self.players.remove(i);
-                if let Some(contents) = self.tile_contents_at(removed_position) {
-                    changes.push((removed_position, contents));
-                }
+        if self.rules.mutual_blockade_draw
+            && self
+                .active_players()
+                .all(|id| self.loss_reason(id).is_some_and(|r| r.is_blockade()))
+        {
+            self.outcome = GameOutcome::Draw {
+                reason: DrawReason::MutualBlockade,
+            };
+            return Ok(TurnResult {
+                outcome: self.outcome,
+                eliminated: Vec::new(),
+                changes,
+                territory: None,
+                undo,
+            });
+        }
+
+        // Check everyone else in turn order first and the mover last, so that if a move makes
+        // both the mover and someone else lose at once, the mover is the one who survives
+        let check_order: Vec<PlayerId> = (1..=self.players.len())
+            .map(|offset| (player + offset) % self.players.len())
+            .filter(|&id| !self.is_eliminated(id))
+            .collect();
+
+        let mut eliminated = Vec::new();
+
+        for id in check_order {
+            if self.outcome != GameOutcome::Ongoing {
+                break;
+            }
+
+            if let Some(reason) = self.loss_reason(id) {
+                self.eliminate(id, reason, &mut changes);
+                eliminated.push((id, reason));
             }
         }
 
-        let winner = if self.players.len() == 1 {
-            Some(self.players[0].color)
-        } else {
-            None
-        };*/
+        let territory = self.score_territory(&mut eliminated, &mut changes);
+
+        self.set_turn(self.next_active_player(self.turn));
+
+        Ok(TurnResult {
+            outcome: self.outcome,
+            eliminated,
+            changes,
+            territory,
+            undo,
+        })
+    }
+
+    // Knock a player out of the game without a move being made, for resigning or timing out
+    pub fn forfeit(&mut self, player: PlayerId, reason: LossReason) -> Result<TurnResult, MoveError> {
+        if self.outcome != GameOutcome::Ongoing {
+            return Err(MoveError::GameOver);
+        }
+
+        if player >= self.players.len() {
+            return Err(MoveError::UnknownPlayer(player));
+        }
+
+        if self.is_eliminated(player) {
+            return Err(MoveError::Eliminated(player));
+        }
 
-        // Since games are always 2 players for now, just check each one in the correct order
-        let mut winner = None;
+        let mut changes = Vec::new();
+        let undo = self.undo_record(player);
+        self.eliminate(player, reason, &mut changes);
+
+        let mut eliminated = vec![(player, reason)];
+        let territory = self.score_territory(&mut eliminated, &mut changes);
 
-        if self.player_lost(1 - player_index) {
-            winner = Some(self.players[player_index].color);
-        } else if self.player_lost(player_index) {
-            winner = Some(self.players[1 - player_index].color);
+        if self.is_eliminated(self.turn) {
+            self.set_turn(self.next_active_player(self.turn));
         }
 
-        self.turn = (self.turn + 1) % 2;
+        Ok(TurnResult {
+            outcome: self.outcome,
+            eliminated,
+            changes,
+            territory,
+            undo,
+        })
+    }
 
-        TurnResult { winner, changes }
+    // Start recording how to undo a move by this player, before anything changes
+    fn undo_record(&self, player: PlayerId) -> Undo {
+        Undo {
+            player,
+            from: self.players[player].pos,
+            also_moved: Vec::new(),
+            scorched: Vec::new(),
+            turn: self.turn,
+            eliminated_len: self.eliminated.len(),
+            outcome: self.outcome,
+            hash: self.hash,
+        }
     }
 
-    // Check if the specified player has lost, due to checkmate or capture
-    pub fn player_lost(&mut self, player_index: usize) -> bool {
-        for (i, player) in self.players.iter().enumerate() {
-            if i != player_index {
-                if player.pos == self.players[player_index].pos {
-                    return true;
-                }
+    // Take back a move (or forfeit, or a round of simultaneous moves) using the undo record from
+    // its TurnResult. Moves have to be undone in the reverse order they were made. Returns the
+    // tiles that changed so they can be redrawn.
+    pub fn unmake_move(&mut self, undo: Undo) -> Vec<(Vector, TileContents)> {
+        let mut touched = vec![self.players[undo.player].pos, undo.from];
+        for &(id, from) in &undo.also_moved {
+            touched.push(self.players[id].pos);
+            touched.push(from);
+            self.players[id].pos = from;
+        }
+
+        for &pos in &undo.scorched {
+            self.set_scorch_state(pos, ScorchState::Empty);
+            touched.push(pos);
+        }
+
+        // Bring back everyone this move eliminated
+        for (id, _) in self.eliminated.drain(undo.eliminated_len..) {
+            touched.push(self.players[id].pos);
+        }
+
+        self.players[undo.player].pos = undo.from;
+        self.turn = undo.turn;
+        self.outcome = undo.outcome;
+        self.hash = undo.hash;
+        self.update_occupied();
+
+        touched
+            .into_iter()
+            .filter_map(|pos| self.tile_contents_at(pos).map(|contents| (pos, contents)))
+            .collect()
+    }
+
+    // Mark a player as eliminated and end the game if there's only one left
+    fn eliminate(
+        &mut self,
+        player: PlayerId,
+        reason: LossReason,
+        changes: &mut Vec<(Vector, TileContents)>,
+    ) {
+        self.hash ^= self.player_key(player);
+        self.eliminated.push((player, reason));
+        self.update_occupied();
+
+        // The eliminated player's soldier disappears, so redraw whatever is under it
+        let removed_position = self.players[player].pos;
+        if let Some(contents) = self.tile_contents_at(removed_position) {
+            changes.push((removed_position, contents));
+        }
+
+        if let Some(outcome) = self.finished_outcome(reason) {
+            self.outcome = outcome;
+        }
+    }
+
+    // Check if the specified player has lost, due to capture or having nowhere left to move
+    // Players that don't exist count as having lost, since they can't still be in the game
+    pub fn player_lost(&self, player: PlayerId) -> bool {
+        self.loss_reason(player).is_some()
+    }
+
+    // Why the specified player has lost, or None if they're still in the game
+    pub fn loss_reason(&self, player: PlayerId) -> Option<LossReason> {
+        let Some(pos) = self.players.get(player).map(|p| p.pos) else {
+            return Some(LossReason::OffBoard);
+        };
+
+        if let Some(&(_, reason)) = self.eliminated.iter().find(|&&(id, _)| id == player) {
+            return Some(reason);
+        }
+
+        if self.hidden.contains(&player) {
+            return None;
+        }
+
+        for id in self.players_in_sight() {
+            if id != player && self.players[id].pos == pos {
+                return Some(LossReason::Captured);
             }
         }
 
-        // If you're on a scorched tile (probably because someone moved over you)
+        // If you're on a scorched tile (because someone moved over you)
         // or you're somehow out of bounds you lose
-        if let Some(ScorchState::Scorched) | None =
-            self.scorch_state_at(self.players[player_index].pos)
+        match self.scorch_state(pos) {
+            None => return Some(LossReason::OffBoard),
+            Some(ScorchState::Scorched | ScorchState::Wall) => return Some(LossReason::RunOver),
+            Some(ScorchState::Empty) => {}
+        }
+
+        // Neighbours off the edge of the board don't count, except on a torus where every tile
+        // has a full set of neighbours around the edges. On a hex board there are six of them.
+        if self.has_legal_move(player) {
+            None
+        } else if self
+            .directions()
+            .iter()
+            .filter_map(|direction| self.translate(pos, direction.to_vector()))
+            .filter_map(|neighbour| self.scorch_state(neighbour))
+            .all(|cell| cell != ScorchState::Empty)
         {
-            return true;
-        }
-
-        [
-            Direction::Up,
-            Direction::Left,
-            Direction::Down,
-            Direction::Right,
-        ]
-        .iter()
-        .map(|direction| {
-            self.scorch_state_at(self.players[player_index].pos + direction.to_vector())
-        })
-        .flatten()
-        .all(|cell| matches!(cell, ScorchState::Scorched))
+            Some(LossReason::Surrounded)
+        } else {
+            Some(LossReason::NoLegalMoves)
+        }
+    }
+
+    // A short description of how the game ended, like "Yellow was surrounded"
+    pub fn outcome_summary(&self) -> String {
+        // Boards can come from over the network, so don't trust the player numbers
+        let color = |id: PlayerId| self.players.get(id).map(|player| player.color);
+        let loser = self.eliminated.last().and_then(|&(id, _)| color(id));
+
+        match self.outcome {
+            GameOutcome::Ongoing => String::from("game in progress"),
+            GameOutcome::Draw { reason } => reason.to_string(),
+            GameOutcome::Win { winner, reason } => match (loser, color(winner)) {
+                (Some(loser), _) => format!("{:?} {}", loser, reason),
+                (None, Some(winner)) => format!("{:?} wins", winner),
+                (None, None) => String::from("game over"),
+            },
+            GameOutcome::TeamWin { team, reason } => match loser {
+                Some(loser) => format!("{:?} {}, so team {} wins", loser, reason, team + 1),
+                None => format!("team {} wins", team + 1),
+            },
+        }
+    }
+}
+
+// A tile's state that can be changed in place, from Board::scorch_state_at_mut
+pub struct ScorchStateMut<'a> {
+    board: &'a mut Board,
+    pos: Vector,
+    state: ScorchState,
+}
+
+impl Deref for ScorchStateMut<'_> {
+    type Target = ScorchState;
+
+    fn deref(&self) -> &ScorchState {
+        &self.state
+    }
+}
+
+impl DerefMut for ScorchStateMut<'_> {
+    fn deref_mut(&mut self) -> &mut ScorchState {
+        &mut self.state
+    }
+}
+
+impl Drop for ScorchStateMut<'_> {
+    fn drop(&mut self) {
+        self.board.set_scorch_state(self.pos, self.state);
     }
 }
 
 impl Default for Board {
-    // Make a default board that's empty with a player in opposite corners
     fn default() -> Self {
-        Board {
-            cells: [[ScorchState::Empty; BOARD_SIZE]; BOARD_SIZE],
-            players: vec![
-                Player {
-                    pos: Vector { x: 0, y: 0 },
-                    color: PlayerColor::Green,
-                },
-                Player {
-                    pos: Vector {
-                        x: (BOARD_SIZE - 1) as isize,
-                        y: (BOARD_SIZE - 1) as isize,
-                    },
-                    color: PlayerColor::Yellow,
-                },
-            ],
-            turn: 0,
+        Board::new(DEFAULT_BOARD_SIZE, DEFAULT_BOARD_SIZE)
+    }
+}
+
+// How boards are serialized, from back when they were stored as a grid of cells
+#[derive(Serialize, Deserialize)]
+struct BoardRepr {
+    width: usize,
+    height: usize,
+    cells: Vec<Vec<ScorchState>>,
+    players: Vec<Player>,
+    rules: Rules,
+    eliminated: Vec<(PlayerId, LossReason)>,
+    turn: PlayerId,
+    outcome: GameOutcome,
+    seed: Option<u64>,
+    topology: Topology,
+    #[serde(default)]
+    hidden: Vec<PlayerId>,
+}
+
+impl From<Board> for BoardRepr {
+    fn from(board: Board) -> Self {
+        let cells = (0..board.height as isize)
+            .map(|y| {
+                (0..board.width as isize)
+                    .map(|x| {
+                        board
+                            .scorch_state(Vector { x, y })
+                            .unwrap_or(ScorchState::Empty)
+                    })
+                    .collect()
+            })
+            .collect();
+
+        BoardRepr {
+            width: board.width,
+            height: board.height,
+            cells,
+            players: board.players,
+            rules: board.rules,
+            eliminated: board.eliminated,
+            turn: board.turn,
+            outcome: board.outcome,
+            seed: board.seed,
+            topology: board.layout.topology,
+            hidden: board.hidden,
+        }
+    }
+}
+
+impl TryFrom<BoardRepr> for Board {
+    type Error = &'static str;
+
+    fn try_from(repr: BoardRepr) -> Result<Self, Self::Error> {
+        // Checked before making the layout, which can't handle boards with no tiles or sizes
+        // that overflow
+        if repr.width == 0 || repr.height == 0 {
+            return Err("board has no tiles");
+        }
+        if repr.width.checked_mul(repr.height).is_none_or(|cells| cells > MAX_CELLS) {
+            return Err("board is too big");
+        }
+
+        if repr.cells.len() != repr.height || repr.cells.iter().any(|row| row.len() != repr.width)
+        {
+            return Err("cells don't match the board dimensions");
+        }
+
+        let mut board = Board {
+            width: repr.width,
+            height: repr.height,
+            layout: Layout::new(repr.width, repr.height, repr.topology),
+            scorched: Bitboard::EMPTY,
+            walls: Bitboard::EMPTY,
+            occupied: Bitboard::EMPTY,
+            players: repr.players,
+            rules: repr.rules,
+            eliminated: repr.eliminated,
+            turn: repr.turn,
+            outcome: repr.outcome,
+            seed: repr.seed,
+            hidden: repr.hidden,
+            hash: 0,
+        };
+
+        for (y, row) in repr.cells.iter().enumerate() {
+            for (x, &state) in row.iter().enumerate() {
+                let pos = Vector {
+                    x: x as isize,
+                    y: y as isize,
+                };
+                board.set_scorch_state(pos, state);
+            }
         }
+
+        board.update_occupied();
+        board.hash = board.compute_hash();
+        Ok(board)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn move_lengths_must_fit_on_the_board() {
+        let mut board = Board::with_topology(5, 3, 2, Topology::Square);
+        board.rules.move_lengths = vec![1, 5];
+        assert!(board.is_consistent());
+
+        board.rules.move_lengths = vec![1, 6];
+        assert!(!board.is_consistent());
+
+        board.rules.move_lengths = vec![0, 1];
+        assert!(!board.is_consistent());
+
+        board.rules.move_lengths = vec![];
+        assert!(!board.is_consistent());
+    }
+
+    #[test]
+    fn players_must_be_on_the_board() {
+        let board = Board::with_topology(5, 5, 3, Topology::Square);
+        assert!(board.is_consistent());
+
+        let mut off_board = board.clone();
+        off_board.players[1].pos = Vector { x: 5, y: 0 };
+        assert!(!off_board.is_consistent());
+
+        let mut on_wall = board.clone();
+        on_wall.set_scorch_state(Vector { x: 2, y: 2 }, ScorchState::Wall);
+        on_wall.players[1].pos = Vector { x: 2, y: 2 };
+        assert!(!on_wall.is_consistent());
+
+        let mut stacked = board.clone();
+        stacked.players[1].pos = stacked.players[0].pos;
+        assert!(!stacked.is_consistent());
+
+        // Unless one of them was captured there
+        stacked.eliminated.push((1, LossReason::Captured));
+        assert!(stacked.is_consistent());
+    }
+
+    #[test]
+    fn eliminations_and_outcome_must_make_sense() {
+        let mut board = Board::with_topology(5, 5, 3, Topology::Square);
+        board.eliminated.push((2, LossReason::Surrounded));
+        assert!(board.is_consistent());
+
+        let mut twice = board.clone();
+        twice.eliminated.push((2, LossReason::Captured));
+        assert!(!twice.is_consistent());
+
+        let mut winner = board.clone();
+        winner.outcome = GameOutcome::Win {
+            winner: 3,
+            reason: LossReason::Surrounded,
+        };
+        assert!(!winner.is_consistent());
+
+        let mut team = board.clone();
+        team.set_teams(2);
+        team.outcome = GameOutcome::TeamWin {
+            team: 1,
+            reason: LossReason::Surrounded,
+        };
+        assert!(team.is_consistent());
+        team.outcome = GameOutcome::TeamWin {
+            team: 2,
+            reason: LossReason::Surrounded,
+        };
+        assert!(!team.is_consistent());
+        assert_eq!(team.outcome_summary(), "Blue was surrounded, so team 3 wins");
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn old_scorch_state_api() {
+        let mut board = Board::default();
+        let pos = Vector { x: 3, y: 4 };
+        assert!(board.scorch_state_at(pos) == Some(&ScorchState::Empty));
+
+        *board.scorch_state_at_mut(pos).unwrap() = ScorchState::Scorched;
+        assert!(board.scorch_state(pos) == Some(ScorchState::Scorched));
+        assert!(board.scorch_state_at(pos) == Some(&ScorchState::Scorched));
+        assert_eq!(board.hash(), board.compute_hash());
+
+        assert!(board.scorch_state_at_mut(Vector { x: -1, y: 0 }).is_none());
+    }
+
+    #[test]
+    fn torus_wraps_around_the_edges() {
+        let mut board = Board::with_topology(5, 4, 2, Topology::Torus);
+        let corner = Vector { x: 0, y: 0 };
+        let step = |dir: Direction| board.translate(corner, dir.to_vector());
+        assert_eq!(step(Direction::Left), Some(Vector { x: 4, y: 0 }));
+        assert_eq!(step(Direction::Up), Some(Vector { x: 0, y: 3 }));
+        assert_eq!(step(Direction::UpLeft), Some(Vector { x: 4, y: 3 }));
+
+        let square = Board::with_topology(5, 4, 2, Topology::Square);
+        assert_eq!(square.translate(corner, Direction::Left.to_vector()), None);
+
+        // Moving left from the first column lands in the last one, scorching the tile left behind
+        let left = Move {
+            dir: Direction::Left,
+            len: 2,
+        };
+        assert!(board.legal_moves(0).contains(&left));
+        board.make_move(0, left).unwrap();
+        assert_eq!(board.players()[0].pos, Vector { x: 3, y: 0 });
+        assert!(board.scorch_state(Vector { x: 4, y: 0 }) == Some(ScorchState::Scorched));
+        assert!(board.scorch_state(corner) == Some(ScorchState::Scorched));
+        assert_eq!(board.hash(), board.compute_hash());
+    }
+
+    #[test]
+    fn torus_players_can_escape_over_the_edge() {
+        let mut torus = Board::with_topology(5, 5, 2, Topology::Torus);
+        let mut square = Board::with_topology(5, 5, 2, Topology::Square);
+        for board in [&mut torus, &mut square] {
+            board.set_scorch_state(Vector { x: 1, y: 0 }, ScorchState::Scorched);
+            board.set_scorch_state(Vector { x: 0, y: 1 }, ScorchState::Scorched);
+        }
+
+        // Green is boxed into the corner on a square board, but not when the edges wrap around
+        assert_eq!(square.loss_reason(0), Some(LossReason::Surrounded));
+        assert!(!torus.player_lost(0));
+        assert_eq!(torus.legal_moves(0).len(), 4);
+
+        torus.set_scorch_state(Vector { x: 4, y: 0 }, ScorchState::Wall);
+        torus.set_scorch_state(Vector { x: 0, y: 4 }, ScorchState::Scorched);
+        assert_eq!(torus.loss_reason(0), Some(LossReason::Surrounded));
+    }
+
+    #[test]
+    fn hex_neighbours_around_the_edges() {
+        let board = Board::with_topology(5, 4, 3, Topology::Hex);
+        let neighbours = |x, y| {
+            let pos = Vector { x, y };
+            board
+                .directions()
+                .iter()
+                .filter_map(|dir| board.translate(pos, dir.to_vector()))
+                .collect::<Vec<_>>()
+        };
+
+        // The rhombus has two sharp corners with two neighbours, and two blunt ones with three
+        assert_eq!(neighbours(0, 0), [Vector { x: 1, y: 0 }, Vector { x: 0, y: 1 }]);
+        assert_eq!(neighbours(4, 3), [Vector { x: 4, y: 2 }, Vector { x: 3, y: 3 }]);
+        assert_eq!(
+            neighbours(4, 0),
+            [Vector { x: 4, y: 1 }, Vector { x: 3, y: 1 }, Vector { x: 3, y: 0 }]
+        );
+        assert_eq!(
+            neighbours(0, 3),
+            [Vector { x: 0, y: 2 }, Vector { x: 1, y: 2 }, Vector { x: 1, y: 3 }]
+        );
+
+        // Tiles along an edge lose the two neighbours past it
+        assert_eq!(neighbours(2, 0).len(), 4);
+        assert_eq!(neighbours(2, 3).len(), 4);
+        assert_eq!(neighbours(0, 1).len(), 4);
+        assert_eq!(neighbours(4, 2).len(), 4);
+        assert_eq!(neighbours(2, 1).len(), 6);
+    }
+
+    #[test]
+    fn hex_players_surrounded_in_the_corners() {
+        let mut board = Board::with_topology(5, 4, 3, Topology::Hex);
+
+        // Green starts in a sharp corner, so two scorched tiles are enough to trap them
+        board.set_scorch_state(Vector { x: 1, y: 0 }, ScorchState::Scorched);
+        assert!(!board.player_lost(0));
+        board.set_scorch_state(Vector { x: 0, y: 1 }, ScorchState::Scorched);
+        assert_eq!(board.loss_reason(0), Some(LossReason::Surrounded));
+
+        // Blue starts in a blunt corner and can still get out down-left
+        board.set_scorch_state(Vector { x: 3, y: 0 }, ScorchState::Scorched);
+        board.set_scorch_state(Vector { x: 4, y: 1 }, ScorchState::Wall);
+        assert!(!board.player_lost(2));
+        let moves = board.legal_moves(2);
+        assert_eq!(moves.len(), 2);
+        assert!(moves.iter().all(|m| m.dir == Direction::DownLeft));
+        board.set_scorch_state(Vector { x: 3, y: 1 }, ScorchState::Scorched);
+        assert_eq!(board.loss_reason(2), Some(LossReason::Surrounded));
+    }
+
+    #[test]
+    fn two_against_two() {
+        // Green and Blue against Yellow and Magenta, taking turns in that order
+        let mut board: Board =
+            "5x5 5./5./5./5./5. G@0.0:0,Y@0.2:1,B@2.0:0,M@2.2:1 G".parse().unwrap();
+        assert!(board.are_teammates(0, 2) && board.are_teammates(1, 3));
+        assert!(!board.are_teammates(0, 1) && !board.are_teammates(0, 0));
+
+        // Green can't land on Blue, but can capture Yellow
+        let onto_blue = Move {
+            dir: Direction::Right,
+            len: 2,
+        };
+        assert!(!board.legal_moves(0).contains(&onto_blue));
+        assert_eq!(board.make_move(0, onto_blue).err(), Some(MoveError::BlockedPath));
+
+        let down = Move {
+            dir: Direction::Down,
+            len: 2,
+        };
+        let res = board.make_move(0, down).unwrap();
+        assert_eq!(res.eliminated, [(1, LossReason::Captured)]);
+        assert!(board.outcome == GameOutcome::Ongoing);
+        assert_eq!(board.turn(), 2);
+
+        // Blue capturing Magenta leaves only Green and Blue, so their team wins together
+        board.make_move(2, down).unwrap();
+        assert!(
+            board.outcome
+                == GameOutcome::TeamWin {
+                    team: 0,
+                    reason: LossReason::Captured
+                }
+        );
+        assert!(board.is_winner(0) && board.is_winner(2));
+        assert!(!board.is_winner(1) && !board.is_winner(3));
+        assert!(board.is_consistent());
+    }
+
+    // A number from 0 up to but not including n, from a splitmix64 counter like generate.rs uses
+    fn random(state: &mut u64, n: usize) -> usize {
+        *state = state.wrapping_add(1);
+        (zobrist::mix(*state) % n as u64) as usize
+    }
+
+    #[test]
+    fn random_playouts_keep_the_hash_and_undo_exactly() {
+        let topologies = [Topology::Square, Topology::Torus, Topology::Hex];
+        let mut rng = 0;
+        let (mut captures, mut eliminations, mut rounds) = (0, 0, 0);
+
+        for game in 0..300 {
+            let mut board = Board::with_topology(7, 7, 2 + game % 4, topologies[game % 3]);
+            board.rules = Rules {
+                move_lengths: if game % 2 == 0 { vec![1, 2] } else { vec![1, 3] },
+                diagonal_moves: game % 5 < 2,
+                jumping: game % 7 == 0,
+                scorch: if game % 6 == 0 { ScorchRule::Origin } else { ScorchRule::Path },
+                territory: game % 9 == 0,
+                simultaneous: game % 4 == 3,
+                ..Default::default()
+            };
+
+            let mut history = Vec::new();
+            while board.outcome == GameOutcome::Ongoing {
+                let before = board.clone();
+                let res = if board.rules.simultaneous {
+                    let moves: Option<Vec<Move>> = board
+                        .active_players()
+                        .map(|id| {
+                            let legal = board.legal_moves(id);
+                            (!legal.is_empty()).then(|| legal[random(&mut rng, legal.len())])
+                        })
+                        .collect();
+                    let Some(moves) = moves else { break };
+                    rounds += 1;
+                    board.resolve_simultaneous(&moves).unwrap()
+                } else if random(&mut rng, 40) == 0 {
+                    board.forfeit(board.turn, LossReason::Resigned).unwrap()
+                } else {
+                    let legal = board.legal_moves(board.turn);
+                    if legal.is_empty() {
+                        break;
+                    }
+                    board.make_move(board.turn, legal[random(&mut rng, legal.len())]).unwrap()
+                };
+
+                assert_eq!(board.hash(), board.compute_hash());
+                eliminations += res.eliminated.len();
+                captures += res
+                    .eliminated
+                    .iter()
+                    .filter(|&&(_, reason)| reason == LossReason::Captured)
+                    .count();
+                history.push((before, res.undo));
+            }
+
+            while let Some((before, undo)) = history.pop() {
+                board.unmake_move(undo);
+                assert!(board == before);
+                assert_eq!(board.hash(), board.compute_hash());
+            }
+        }
+
+        // Make sure the playouts went through everything that changes the hash in unusual ways
+        assert!(captures > 0 && eliminations > captures && rounds > 0);
     }
 }
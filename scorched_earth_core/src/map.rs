@@ -0,0 +1,205 @@
+use thiserror::Error;
+
+use crate::{Board, Player, PlayerColor, ScorchState, Topology, Vector, MAX_CELLS, MAX_PLAYERS};
+
+// Maps are drawn as a grid of characters, one line per row:
+//
+//     1.....
+//     ..##..
+//     ..##..
+//     .....2
+//
+// where . is an empty tile, # is a wall, x is a tile that starts off scorched, and the digits are
+// where each player starts (on an empty tile). Players are numbered from 1 in turn order, and get
+// colors in the usual order. Blank lines before and after the grid are ignored.
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum MapError {
+    #[error("the map is empty")]
+    Empty,
+    #[error("row {0} is a different length to the first row")]
+    UnevenRows(usize),
+    #[error("the map has more than {MAX_CELLS} tiles")]
+    TooBig,
+    #[error("unknown tile {tile:?} at row {row}, column {column}")]
+    InvalidTile {
+        tile: char,
+        row: usize,
+        column: usize,
+    },
+    #[error("player {0} starts in more than one place")]
+    DuplicatePlayer(usize),
+    #[error("player {0} has no starting place")]
+    MissingPlayer(usize),
+    #[error("maps need starting places for between 2 and {MAX_PLAYERS} players")]
+    WrongPlayerCount,
+}
+
+impl Board {
+    // Make a board from a map, with the default rules
+    pub fn from_map(map: &str) -> Result<Board, MapError> {
+        let rows: Vec<&str> = map
+            .trim_matches(|c| c == '\n' || c == '\r')
+            .lines()
+            .map(|row| row.trim_end())
+            .collect();
+
+        let width = rows.first().map_or(0, |row| row.chars().count());
+        let height = rows.len();
+        if width == 0 {
+            return Err(MapError::Empty);
+        }
+        if width * height > MAX_CELLS {
+            return Err(MapError::TooBig);
+        }
+
+        let mut board = Board::blank(width, height, Topology::Square);
+        let mut starts: [Option<Vector>; MAX_PLAYERS] = [None; MAX_PLAYERS];
+
+        for (y, row) in rows.iter().enumerate() {
+            if row.chars().count() != width {
+                return Err(MapError::UnevenRows(y + 1));
+            }
+
+            for (x, tile) in row.chars().enumerate() {
+                let pos = Vector {
+                    x: x as isize,
+                    y: y as isize,
+                };
+
+                let state = match tile {
+                    '.' => ScorchState::Empty,
+                    'x' => ScorchState::Scorched,
+                    '#' => ScorchState::Wall,
+                    '1'..='9' => {
+                        let number = tile as usize - '0' as usize;
+                        let start = starts
+                            .get_mut(number - 1)
+                            .ok_or(MapError::WrongPlayerCount)?;
+                        if start.replace(pos).is_some() {
+                            return Err(MapError::DuplicatePlayer(number));
+                        }
+                        ScorchState::Empty
+                    }
+                    _ => {
+                        return Err(MapError::InvalidTile {
+                            tile,
+                            row: y + 1,
+                            column: x + 1,
+                        })
+                    }
+                };
+                board.set_scorch_state(pos, state);
+            }
+        }
+
+        // Everyone up to the highest numbered player needs a place to start
+        let player_count = starts.iter().rposition(Option::is_some).map_or(0, |i| i + 1);
+        if player_count < 2 {
+            return Err(MapError::WrongPlayerCount);
+        }
+
+        board.players = starts[..player_count]
+            .iter()
+            .zip(PlayerColor::ALL)
+            .enumerate()
+            .map(|(i, (start, color))| {
+                start
+                    .map(|pos| Player {
+                        pos,
+                        color,
+                        team: None,
+                    })
+                    .ok_or(MapError::MissingPlayer(i + 1))
+            })
+            .collect::<Result<_, _>>()?;
+
+        board.update_occupied();
+        board.hash = board.compute_hash();
+        Ok(board)
+    }
+
+    // Draw the board as a map, with every player where they are now
+    pub fn to_map(&self) -> String {
+        let mut map = String::new();
+
+        for y in 0..self.height as isize {
+            for x in 0..self.width as isize {
+                let pos = Vector { x, y };
+                let player = self.players.iter().position(|p| p.pos == pos);
+
+                map.push(match (player, self.scorch_state(pos)) {
+                    (Some(id), _) => char::from_digit(id as u32 + 1, 10).unwrap_or('?'),
+                    (None, Some(ScorchState::Scorched)) => 'x',
+                    (None, Some(ScorchState::Wall)) => '#',
+                    (None, _) => '.',
+                });
+            }
+            map.push('\n');
+        }
+
+        map
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(map: &str) -> Option<MapError> {
+        Board::from_map(map).err()
+    }
+
+    #[test]
+    fn reads_a_map() {
+        let map = "1.....\n..##..\n..#x..\n.....2\n";
+        let board = Board::from_map(&format!("\n\n{map}\n")).unwrap();
+        assert_eq!((board.width(), board.height()), (6, 4));
+        assert_eq!(board.players()[1].pos, Vector { x: 5, y: 3 });
+        assert!(board.scorch_state(Vector { x: 3, y: 2 }) == Some(ScorchState::Scorched));
+        assert!(board.is_consistent());
+        assert_eq!(board.to_map(), map);
+    }
+
+    #[test]
+    fn ragged_rows() {
+        assert_eq!(error("1...\n...\n...2"), Some(MapError::UnevenRows(2)));
+        assert_eq!(error("1..\n...\n...2"), Some(MapError::UnevenRows(3)));
+
+        // Spaces at the end of a row don't count
+        assert!(Board::from_map("1..  \n...\n..2").is_ok());
+    }
+
+    #[test]
+    fn unknown_tiles() {
+        assert_eq!(
+            error("1..\n.o.\n..2"),
+            Some(MapError::InvalidTile {
+                tile: 'o',
+                row: 2,
+                column: 2
+            })
+        );
+        assert_eq!(
+            error("1. .\n...2"),
+            Some(MapError::InvalidTile {
+                tile: ' ',
+                row: 1,
+                column: 3
+            })
+        );
+        assert_eq!(error("\n\n"), Some(MapError::Empty));
+    }
+
+    #[test]
+    fn starting_places() {
+        assert_eq!(error("1..\n...\n..1"), Some(MapError::DuplicatePlayer(1)));
+        assert_eq!(error("1..\n...\n..3"), Some(MapError::MissingPlayer(2)));
+        assert_eq!(error("1..\n...\n..."), Some(MapError::WrongPlayerCount));
+        assert_eq!(error("...\n...\n..."), Some(MapError::WrongPlayerCount));
+        assert_eq!(error("1.2\n...\n..6"), Some(MapError::WrongPlayerCount));
+
+        let board = Board::from_map("1.2\n.5.\n4.3").unwrap();
+        assert_eq!(board.players().len(), 5);
+    }
+}
@@ -0,0 +1,465 @@
+use std::str::FromStr;
+
+use thiserror::Error;
+
+use crate::{
+    Board, LossReason, Player, PlayerColor, ScorchState, Topology, Vector, MAX_CELLS, MAX_PLAYERS,
+};
+
+// A short text version of a position, for pasting into bug reports. It looks like
+//
+//     5x3 5./.x3./2x3. G@0.0,Y@4.2,B@4.0!s Y
+//
+// which is four fields separated by spaces:
+//
+// - The width and height
+// - Each row from top to bottom, separated by slashes. A row is runs of tiles written as a count
+//   and then . for empty, x for scorched or # for a wall, where a count of 1 is left out.
+// - Each player as the first letter of their color, @, and their x.y position. Players on a team
+//   have : and the team number after that, and eliminated players have ! and a letter for why
+//   they lost (see reason_letter).
+// - The first letter of the color of the player whose turn it is
+//
+// Rules and topology aren't included, so they're the defaults when reading a position back in.
+// Eliminated players are listed in player order rather than the order they lost in, and a
+// finished game is always read back as a win for whoever (or whichever team) is left.
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum NotationError {
+    #[error("expected 4 fields separated by spaces")]
+    WrongFieldCount,
+    #[error("invalid board size {0:?}")]
+    InvalidSize(String),
+    #[error("board is too big")]
+    TooBig,
+    #[error("expected {expected} rows, found {found}")]
+    WrongRowCount { expected: usize, found: usize },
+    #[error("row {0} is invalid")]
+    InvalidRow(usize),
+    #[error("row {row} has {found} tiles instead of {expected}")]
+    WrongRowLength {
+        row: usize,
+        expected: usize,
+        found: usize,
+    },
+    #[error("invalid player {0:?}")]
+    InvalidPlayer(String),
+    #[error("games need between 2 and {MAX_PLAYERS} players")]
+    WrongPlayerCount,
+    #[error("invalid player to move {0:?}")]
+    InvalidTurn(String),
+    #[error("the position can't happen in a game")]
+    Inconsistent,
+}
+
+fn color_letter(color: PlayerColor) -> char {
+    match color {
+        PlayerColor::Blue => 'B',
+        PlayerColor::Cyan => 'C',
+        PlayerColor::Yellow => 'Y',
+        PlayerColor::Green => 'G',
+        PlayerColor::Magenta => 'M',
+    }
+}
+
+fn color_from_letter(letter: char) -> Option<PlayerColor> {
+    PlayerColor::ALL
+        .into_iter()
+        .find(|&color| color_letter(color) == letter)
+}
+
+fn reason_letter(reason: LossReason) -> char {
+    match reason {
+        LossReason::Captured => 'c',
+        LossReason::RunOver => 'r',
+        LossReason::Surrounded => 's',
+        LossReason::NoLegalMoves => 'n',
+        LossReason::OffBoard => 'o',
+        LossReason::Resigned => 'q',
+        LossReason::Timeout => 't',
+        LossReason::Collided => 'h',
+        LossReason::LessRoom => 'l',
+    }
+}
+
+fn reason_from_letter(letter: char) -> Option<LossReason> {
+    [
+        LossReason::Captured,
+        LossReason::RunOver,
+        LossReason::Surrounded,
+        LossReason::NoLegalMoves,
+        LossReason::OffBoard,
+        LossReason::Resigned,
+        LossReason::Timeout,
+        LossReason::Collided,
+        LossReason::LessRoom,
+    ]
+    .into_iter()
+    .find(|&reason| reason_letter(reason) == letter)
+}
+
+impl Board {
+    // Write the position in the notation described at the top of this file
+    pub fn to_notation(&self) -> String {
+        let rows: Vec<String> = (0..self.height as isize)
+            .map(|y| {
+                let mut row = String::new();
+                let mut x = 0;
+                while x < self.width as isize {
+                    let state = self.scorch_state(Vector { x, y });
+                    let mut run = 1;
+                    while x + run < self.width as isize
+                        && self.scorch_state(Vector { x: x + run, y }) == state
+                    {
+                        run += 1;
+                    }
+
+                    if run > 1 {
+                        row.push_str(&run.to_string());
+                    }
+                    row.push(match state {
+                        Some(ScorchState::Scorched) => 'x',
+                        Some(ScorchState::Wall) => '#',
+                        _ => '.',
+                    });
+                    x += run;
+                }
+                row
+            })
+            .collect();
+
+        let players: Vec<String> = self
+            .players
+            .iter()
+            .enumerate()
+            .map(|(id, player)| {
+                let mut text = format!(
+                    "{}@{}.{}",
+                    color_letter(player.color),
+                    player.pos.x,
+                    player.pos.y
+                );
+                if let Some(team) = player.team {
+                    text.push_str(&format!(":{}", team));
+                }
+                if let Some(&(_, reason)) = self.eliminated.iter().find(|&&(other, _)| other == id)
+                {
+                    text.push('!');
+                    text.push(reason_letter(reason));
+                }
+                text
+            })
+            .collect();
+
+        format!(
+            "{}x{} {} {} {}",
+            self.width,
+            self.height,
+            rows.join("/"),
+            players.join(","),
+            color_letter(self.players[self.turn].color)
+        )
+    }
+}
+
+// Read a row like 2.3x6. into scorch states
+fn parse_row(row: &str) -> Option<Vec<ScorchState>> {
+    let mut cells = Vec::new();
+    let mut count = String::new();
+
+    for c in row.chars() {
+        match c {
+            '0'..='9' => count.push(c),
+            '.' | 'x' | '#' => {
+                let run = if count.is_empty() {
+                    1
+                } else {
+                    count.parse().ok().filter(|&run: &usize| run > 0 && run <= MAX_CELLS)?
+                };
+                count.clear();
+
+                let state = match c {
+                    'x' => ScorchState::Scorched,
+                    '#' => ScorchState::Wall,
+                    _ => ScorchState::Empty,
+                };
+                cells.extend(std::iter::repeat_n(state, run));
+            }
+            _ => return None,
+        }
+    }
+
+    // A count on the end with nothing after it
+    count.is_empty().then_some(cells)
+}
+
+// Read a player like G@3.4, G@3.4:1 or G@3.4:1!c
+fn parse_player(text: &str) -> Option<(Player, Option<LossReason>)> {
+    let (player, reason) = match text.split_once('!') {
+        Some((player, reason)) => {
+            let mut letters = reason.chars();
+            let reason = reason_from_letter(letters.next()?)?;
+            if letters.next().is_some() {
+                return None;
+            }
+            (player, Some(reason))
+        }
+        None => (text, None),
+    };
+
+    let (color, pos) = player.split_once('@')?;
+    let mut letters = color.chars();
+    let color = color_from_letter(letters.next()?)?;
+    if letters.next().is_some() {
+        return None;
+    }
+
+    let (pos, team) = match pos.split_once(':') {
+        Some((pos, team)) => (pos, Some(team.parse().ok()?)),
+        None => (pos, None),
+    };
+
+    let (x, y) = pos.split_once('.')?;
+    let pos = Vector {
+        x: x.parse().ok()?,
+        y: y.parse().ok()?,
+    };
+
+    Some((Player { pos, color, team }, reason))
+}
+
+impl FromStr for Board {
+    type Err = NotationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = s.split_whitespace().collect();
+        let [size, rows, players, turn] = fields[..] else {
+            return Err(NotationError::WrongFieldCount);
+        };
+
+        let invalid_size = || NotationError::InvalidSize(size.to_string());
+        let (width, height) = size.split_once('x').ok_or_else(invalid_size)?;
+        let width: usize = width.parse().map_err(|_| invalid_size())?;
+        let height: usize = height.parse().map_err(|_| invalid_size())?;
+        if width == 0 || height == 0 {
+            return Err(invalid_size());
+        }
+        if width.saturating_mul(height) > MAX_CELLS {
+            return Err(NotationError::TooBig);
+        }
+
+        let rows: Vec<&str> = rows.split('/').collect();
+        if rows.len() != height {
+            return Err(NotationError::WrongRowCount {
+                expected: height,
+                found: rows.len(),
+            });
+        }
+
+        let mut board = Board::blank(width, height, Topology::Square);
+
+        for (y, row) in rows.into_iter().enumerate() {
+            let cells = parse_row(row).ok_or(NotationError::InvalidRow(y))?;
+            if cells.len() != width {
+                return Err(NotationError::WrongRowLength {
+                    row: y,
+                    expected: width,
+                    found: cells.len(),
+                });
+            }
+
+            for (x, state) in cells.into_iter().enumerate() {
+                let pos = Vector {
+                    x: x as isize,
+                    y: y as isize,
+                };
+                board.set_scorch_state(pos, state);
+            }
+        }
+
+        board.players.clear();
+        board.eliminated.clear();
+        for (id, text) in players.split(',').enumerate() {
+            let (player, reason) = parse_player(text)
+                .filter(|(player, _)| board.players.iter().all(|p| p.color != player.color))
+                .ok_or_else(|| NotationError::InvalidPlayer(text.to_string()))?;
+            board.players.push(player);
+            if let Some(reason) = reason {
+                board.eliminated.push((id, reason));
+            }
+        }
+
+        if !(2..=MAX_PLAYERS).contains(&board.players.len()) {
+            return Err(NotationError::WrongPlayerCount);
+        }
+
+        let invalid_turn = || NotationError::InvalidTurn(turn.to_string());
+        let mut letters = turn.chars();
+        let turn_color = letters.next().and_then(color_from_letter);
+        if letters.next().is_some() {
+            return Err(invalid_turn());
+        }
+        board.turn = turn_color
+            .and_then(|color| board.players.iter().position(|p| p.color == color))
+            .ok_or_else(invalid_turn)?;
+
+        if let Some(outcome) = board
+            .eliminated
+            .last()
+            .and_then(|&(_, reason)| board.finished_outcome(reason))
+        {
+            board.outcome = outcome;
+        }
+
+        // Players off the board or on top of each other, and so on
+        if !board.is_consistent() {
+            return Err(NotationError::Inconsistent);
+        }
+
+        board.update_occupied();
+        board.hash = board.compute_hash();
+        Ok(board)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GameOutcome;
+
+    // Reading a position and writing it back out should give exactly the same text
+    fn assert_round_trip(notation: &str) -> Board {
+        let board: Board = notation.parse().unwrap();
+        assert_eq!(board.to_notation(), notation);
+        let again: Board = board.to_notation().parse().unwrap();
+        assert_eq!(again.to_notation(), notation);
+        assert!(again == board);
+        board
+    }
+
+    #[test]
+    fn starting_position() {
+        let board = Board::default();
+        let notation = board.to_notation();
+        assert_eq!(
+            notation,
+            "11x11 11./11./11./11./11./11./11./11./11./11./11. G@0.0,Y@10.10 G"
+        );
+        assert!(assert_round_trip(&notation) == board);
+    }
+
+    #[test]
+    fn scorched_tiles_and_walls() {
+        let board = assert_round_trip("6x3 6./.x3#x/2x#3. G@0.0,Y@5.2 Y");
+        assert!(board.scorch_state(Vector { x: 1, y: 1 }) == Some(ScorchState::Scorched));
+        assert!(board.scorch_state(Vector { x: 4, y: 1 }) == Some(ScorchState::Wall));
+        assert!(board.scorch_state(Vector { x: 2, y: 2 }) == Some(ScorchState::Wall));
+        assert!(board.scorch_state(Vector { x: 3, y: 2 }) == Some(ScorchState::Empty));
+        assert_eq!(board.turn, 1);
+    }
+
+    #[test]
+    fn teams() {
+        let board = assert_round_trip("4x4 4./4./4./4. G@0.0:0,Y@3.3:1,B@3.0:0,M@0.3:1 B");
+        let teams: Vec<Option<usize>> = board.players.iter().map(|p| p.team).collect();
+        assert_eq!(teams, [Some(0), Some(1), Some(0), Some(1)]);
+        assert_eq!(board.turn, 2);
+    }
+
+    #[test]
+    fn eliminated_players() {
+        let board = assert_round_trip("5x3 5./.x3./2x3. G@0.0,Y@4.2,B@4.0!s Y");
+        assert_eq!(board.eliminated, [(2, LossReason::Surrounded)]);
+        assert_eq!(board.outcome, GameOutcome::Ongoing);
+
+        let board = assert_round_trip("3x1 .2x G@0.0,Y@2.0!r G");
+        assert_eq!(
+            board.outcome,
+            GameOutcome::Win {
+                winner: 0,
+                reason: LossReason::RunOver
+            }
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let parse = |notation: &str| notation.parse::<Board>().err();
+
+        assert_eq!(parse("3x1 3."), Some(NotationError::WrongFieldCount));
+        assert_eq!(
+            parse("0x3 3./3./3. G@0.0,Y@2.2 G"),
+            Some(NotationError::InvalidSize(String::from("0x3")))
+        );
+        assert_eq!(
+            parse("3by3 3./3./3. G@0.0,Y@2.2 G"),
+            Some(NotationError::InvalidSize(String::from("3by3")))
+        );
+        assert_eq!(
+            parse("100x100 100. G@0.0,Y@2.2 G"),
+            Some(NotationError::TooBig)
+        );
+        assert_eq!(
+            parse("3x3 3./3. G@0.0,Y@2.1 G"),
+            Some(NotationError::WrongRowCount {
+                expected: 3,
+                found: 2
+            })
+        );
+        assert_eq!(
+            parse("3x2 3./2. G@0.0,Y@2.1 G"),
+            Some(NotationError::WrongRowLength {
+                row: 1,
+                expected: 3,
+                found: 2
+            })
+        );
+        assert_eq!(
+            parse("3x1 3z G@0.0,Y@2.0 G"),
+            Some(NotationError::InvalidRow(0))
+        );
+        assert_eq!(
+            parse("3x1 3. G@0.0,G@2.0 G"),
+            Some(NotationError::InvalidPlayer(String::from("G@2.0")))
+        );
+        assert_eq!(
+            parse("3x1 3. G@0.0 G"),
+            Some(NotationError::WrongPlayerCount)
+        );
+        assert_eq!(
+            parse("3x1 3. G@0.0,Y@2.0 B"),
+            Some(NotationError::InvalidTurn(String::from("B")))
+        );
+        assert_eq!(
+            parse("3x1 3. G@0.0,Y@2.0 GY"),
+            Some(NotationError::InvalidTurn(String::from("GY")))
+        );
+    }
+
+    #[test]
+    fn impossible_positions() {
+        let parse = |notation: &str| notation.parse::<Board>().err();
+
+        // Off the board
+        assert_eq!(
+            parse("3x3 3./3./3. G@0.0,Y@3.1 G"),
+            Some(NotationError::Inconsistent)
+        );
+        // On a wall
+        assert_eq!(
+            parse("3x3 3./.#./3. G@0.0,Y@1.1 G"),
+            Some(NotationError::Inconsistent)
+        );
+        // Two players on one tile, unless one of them was captured there
+        assert_eq!(
+            parse("3x3 3./3./3. G@0.0,Y@2.2,B@2.2 G"),
+            Some(NotationError::Inconsistent)
+        );
+        assert!(parse("3x3 3./3./3. G@0.0,Y@2.2,B@2.2!c G").is_none());
+        // The player to move is already out
+        assert_eq!(
+            parse("3x3 3./3./3. G@0.0,Y@2.2,B@2.0!s B"),
+            Some(NotationError::Inconsistent)
+        );
+    }
+}
@@ -0,0 +1,493 @@
+use std::{fmt, str::FromStr};
+
+use thiserror::Error;
+
+use crate::{
+    Board, Direction, GameOutcome, Move, MoveError, NotationError, Rules, ScorchRule, Topology,
+    TurnResult, MAX_CELLS,
+};
+
+// A whole game written down as text, a bit like chess PGN. It looks like
+//
+//     [Date "2023-02-18"]
+//     [Players "Green, Yellow"]
+//     [Rules "lengths=1,2 scorch=path"]
+//     [Start "11x11 11./11./11./11./11./11./11./11./11./11./11. G@0.0,Y@10.10 G"]
+//     [Result "Yellow was captured"]
+//
+//     D2 L2 R1 U1 D2 L1
+//
+// The tags at the top say who played and with which rules and starting position (in the
+// notation from notation.rs), plus a Seed tag if the map was generated and a Topology tag if the
+// board isn't square. Quotes and backslashes in tag values get a backslash in front. Then the
+// moves are listed in the order they were made. Each move is its direction (U, D, L, R, UL, UR,
+// DL or DR) and then its length. Whose move it is isn't written down since that follows from the
+// turn order. With simultaneous moves, each round is
+// written as the move of every player still in the game, in player order.
+//
+// The Result tag is just for people reading the record, and gets worked out again by replaying
+// the moves when a record is written.
+
+#[derive(Error, Debug)]
+pub enum RecordError {
+    #[error("invalid tag {0:?}")]
+    InvalidTag(String),
+    #[error("invalid move {0:?}")]
+    InvalidMove(String),
+    #[error("invalid rules: {0}")]
+    InvalidRules(String),
+    #[error("invalid start position: {0}")]
+    InvalidStart(#[from] NotationError),
+    #[error("the record has {found} players but the start position has {expected}")]
+    WrongPlayerCount { expected: usize, found: usize },
+    #[error("move {number} is illegal: {error}")]
+    IllegalMove { number: usize, error: MoveError },
+    #[error("moves of length {len} don't fit on a {width}x{height} board")]
+    MoveTooLong {
+        len: usize,
+        width: usize,
+        height: usize,
+    },
+}
+
+#[derive(Clone)]
+pub struct GameRecord {
+    // What to call each player, in player order
+    pub players: Vec<String>,
+    pub date: Option<String>,
+    // The position before the first move, including the rules the game was played with
+    pub start: Board,
+    pub moves: Vec<Move>,
+    // Any other tags, kept so they're written back out again
+    pub tags: Vec<(String, String)>,
+}
+
+impl GameRecord {
+    // Start recording a game from this position, naming the players after their colors
+    pub fn new(start: Board) -> Self {
+        GameRecord {
+            players: start
+                .players
+                .iter()
+                .map(|player| format!("{:?}", player.color))
+                .collect(),
+            date: None,
+            start,
+            moves: Vec::new(),
+            tags: Vec::new(),
+        }
+    }
+
+    // Play every move from the start position, giving the board at the end
+    pub fn replay(&self) -> Result<Board, RecordError> {
+        let mut board = self.start.clone();
+        let mut played = 0;
+        while played < self.moves.len() {
+            let (count, _) = board.play_recorded(&self.moves[played..]).map_err(|error| {
+                RecordError::IllegalMove {
+                    number: played + 1,
+                    error,
+                }
+            })?;
+            played += count;
+        }
+        Ok(board)
+    }
+
+    // How the game ended, or * if it hasn't yet
+    pub fn result(&self) -> String {
+        match self.replay() {
+            Ok(board) if board.outcome != GameOutcome::Ongoing => board.outcome_summary(),
+            _ => String::from("*"),
+        }
+    }
+}
+
+impl fmt::Display for GameRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(date) = &self.date {
+            writeln!(f, "[Date {}]", quote(date))?;
+        }
+        writeln!(f, "[Players {}]", quote(&self.players.join(", ")))?;
+        writeln!(f, "[Rules \"{}\"]", self.start.rules)?;
+        writeln!(f, "[Start \"{}\"]", self.start.to_notation())?;
+        if let Some(seed) = self.start.seed {
+            writeln!(f, "[Seed \"{}\"]", seed)?;
+        }
+        match self.start.topology() {
+            Topology::Square => {}
+            Topology::Torus => writeln!(f, "[Topology \"torus\"]")?,
+            Topology::Hex => writeln!(f, "[Topology \"hex\"]")?,
+        }
+        writeln!(f, "[Result \"{}\"]", self.result())?;
+        for (name, value) in &self.tags {
+            writeln!(f, "[{} {}]", name, quote(value))?;
+        }
+        writeln!(f)?;
+
+        // Keep lines a readable length
+        let mut line_len = 0;
+        for m in &self.moves {
+            let text = m.to_string();
+            if line_len > 0 && line_len + text.len() >= 80 {
+                writeln!(f)?;
+                line_len = 0;
+            } else if line_len > 0 {
+                write!(f, " ")?;
+                line_len += 1;
+            }
+            write!(f, "{}", text)?;
+            line_len += text.len();
+        }
+        writeln!(f)
+    }
+}
+
+// A tag value in quotes, with a backslash before any quote or backslash in it like in PGN, so
+// player names and other tags can have quotes in them
+fn quote(value: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in value.chars() {
+        if c == '"' || c == '\\' {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+// Read a tag line like [Name "value"]. Quotes and backslashes in the value have to be escaped
+// with a backslash.
+fn parse_tag(line: &str) -> Option<(String, String)> {
+    let inner = line.strip_prefix('[')?.strip_suffix(']')?;
+    let (name, value) = inner.split_once(' ')?;
+    let mut chars = value.trim().strip_prefix('"')?.chars();
+
+    let mut value = String::new();
+    loop {
+        match chars.next()? {
+            '\\' => value.push(chars.next()?),
+            '"' => break,
+            c => value.push(c),
+        }
+    }
+
+    // Nothing can come after the closing quote
+    chars.next().is_none().then(|| (name.to_string(), value))
+}
+
+impl FromStr for GameRecord {
+    type Err = RecordError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut players: Option<Vec<String>> = None;
+        let mut date = None;
+        let mut rules = Rules::default();
+        let mut start = None;
+        let mut seed = None;
+        let mut topology = Topology::Square;
+        let mut moves = Vec::new();
+        let mut tags = Vec::new();
+
+        for line in s.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            if line.starts_with('[') {
+                let (name, value) =
+                    parse_tag(line).ok_or_else(|| RecordError::InvalidTag(line.to_string()))?;
+                match name.as_str() {
+                    "Players" => {
+                        players = Some(value.split(',').map(|p| p.trim().to_string()).collect())
+                    }
+                    "Date" => date = Some(value),
+                    "Rules" => rules = value.parse()?,
+                    "Start" => start = Some(value.parse::<Board>()?),
+                    "Seed" => {
+                        seed = Some(
+                            value
+                                .parse()
+                                .map_err(|_| RecordError::InvalidTag(line.to_string()))?,
+                        )
+                    }
+                    "Topology" => {
+                        topology = match value.as_str() {
+                            "square" => Topology::Square,
+                            "torus" => Topology::Torus,
+                            "hex" => Topology::Hex,
+                            _ => return Err(RecordError::InvalidTag(line.to_string())),
+                        }
+                    }
+                    // Worked out from the moves instead
+                    "Result" => {}
+                    _ => tags.push((name, value)),
+                }
+            } else {
+                for text in line.split_whitespace() {
+                    moves.push(text.parse()?);
+                }
+            }
+        }
+
+        let mut start = start.unwrap_or_default();
+        if rules.max_move_len() > start.width.max(start.height) {
+            return Err(RecordError::MoveTooLong {
+                len: rules.max_move_len(),
+                width: start.width,
+                height: start.height,
+            });
+        }
+        start.rules = rules;
+        start.seed = seed;
+        start.set_topology(topology);
+
+        let players: Vec<String> = match players {
+            Some(players) if players.len() != start.players.len() => {
+                return Err(RecordError::WrongPlayerCount {
+                    expected: start.players.len(),
+                    found: players.len(),
+                })
+            }
+            Some(players) => players,
+            None => GameRecord::new(start.clone()).players,
+        };
+
+        Ok(GameRecord {
+            players,
+            date,
+            start,
+            moves,
+            tags,
+        })
+    }
+}
+
+impl Board {
+    // Play the next move from a list of recorded moves, or the next round of them with
+    // simultaneous moves. Gives back how many of the moves were used, along with the result.
+    pub fn play_recorded(&mut self, moves: &[Move]) -> Result<(usize, TurnResult), MoveError> {
+        if self.rules.simultaneous {
+            let round = &moves[..moves.len().min(self.active_players().count())];
+            Ok((round.len(), self.resolve_simultaneous(round)?))
+        } else {
+            let Some(&m) = moves.first() else {
+                return Err(MoveError::WrongMoveCount {
+                    expected: 1,
+                    found: 0,
+                });
+            };
+            Ok((1, self.make_move(self.turn, m)?))
+        }
+    }
+}
+
+impl Direction {
+    // Short name used when writing moves down, like UL for up-left
+    pub fn abbreviation(&self) -> &'static str {
+        match self {
+            Direction::Up => "U",
+            Direction::Down => "D",
+            Direction::Left => "L",
+            Direction::Right => "R",
+            Direction::UpLeft => "UL",
+            Direction::UpRight => "UR",
+            Direction::DownLeft => "DL",
+            Direction::DownRight => "DR",
+        }
+    }
+}
+
+// Moves are written as their direction's abbreviation and length, like U2 or DR1
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.dir.abbreviation(), self.len)
+    }
+}
+
+impl FromStr for Move {
+    type Err = RecordError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || RecordError::InvalidMove(s.to_string());
+
+        let split = s.find(|c: char| c.is_ascii_digit()).ok_or_else(invalid)?;
+        let (dir, len) = s.split_at(split);
+
+        let dir = Direction::ALL
+            .into_iter()
+            .find(|d| d.abbreviation() == dir)
+            .ok_or_else(invalid)?;
+        let len = len.parse().ok().filter(|&len| len > 0).ok_or_else(invalid)?;
+
+        Ok(Move { dir, len })
+    }
+}
+
+// Rules are written as words separated by spaces, with the same names as the TUI's options:
+// lengths=1,2 and scorch=path or scorch=origin, then any of diagonal, jumping, blockade-draw,
+// no-capture, territory and simultaneous, and fog=3 for fog of war
+impl fmt::Display for Rules {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let lengths: Vec<String> = self.move_lengths.iter().map(|len| len.to_string()).collect();
+        write!(f, "lengths={}", lengths.join(","))?;
+
+        for (enabled, name) in [
+            (self.diagonal_moves, "diagonal"),
+            (self.jumping, "jumping"),
+            (self.mutual_blockade_draw, "blockade-draw"),
+            (!self.capture, "no-capture"),
+            (self.territory, "territory"),
+            (self.simultaneous, "simultaneous"),
+        ] {
+            if enabled {
+                write!(f, " {}", name)?;
+            }
+        }
+
+        match self.scorch {
+            ScorchRule::Path => write!(f, " scorch=path")?,
+            ScorchRule::Origin => write!(f, " scorch=origin")?,
+        }
+
+        match self.fog {
+            Some(radius) => write!(f, " fog={}", radius),
+            None => Ok(()),
+        }
+    }
+}
+
+impl FromStr for Rules {
+    type Err = RecordError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut rules = Rules::default();
+
+        for word in s.split_whitespace() {
+            match word.split_once('=') {
+                Some(("lengths", lengths)) => {
+                    rules.move_lengths = lengths
+                        .split(',')
+                        // No board has a side longer than this, so anything longer can't be a
+                        // real move. The start position is checked more closely once it's known.
+                        .map(|len| len.parse().ok().filter(|&len| len > 0 && len <= MAX_CELLS))
+                        .collect::<Option<_>>()
+                        .ok_or_else(|| RecordError::InvalidRules(word.to_string()))?;
+                }
+                Some(("scorch", "path")) => rules.scorch = ScorchRule::Path,
+                Some(("scorch", "origin")) => rules.scorch = ScorchRule::Origin,
+                Some(("fog", radius)) => {
+                    rules.fog = Some(
+                        radius
+                            .parse()
+                            .map_err(|_| RecordError::InvalidRules(word.to_string()))?,
+                    )
+                }
+                None if word == "diagonal" => rules.diagonal_moves = true,
+                None if word == "jumping" => rules.jumping = true,
+                None if word == "blockade-draw" => rules.mutual_blockade_draw = true,
+                None if word == "no-capture" => rules.capture = false,
+                None if word == "territory" => rules.territory = true,
+                None if word == "simultaneous" => rules.simultaneous = true,
+                _ => return Err(RecordError::InvalidRules(word.to_string())),
+            }
+        }
+
+        Ok(rules)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn move_lengths_must_fit_on_the_board() {
+        assert!("lengths=0,1".parse::<Rules>().is_err());
+        assert!(format!("lengths={}", MAX_CELLS + 1).parse::<Rules>().is_err());
+        assert_eq!("lengths=1,3".parse::<Rules>().unwrap().move_lengths, vec![1, 3]);
+
+        let record = |lengths| {
+            format!(
+                "[Rules \"lengths={}\"]\n[Start \"3x3 3./3./3. G@0.0,Y@2.2 G\"]\n",
+                lengths
+            )
+            .parse::<GameRecord>()
+        };
+        assert!(record("1,3").is_ok());
+        assert!(matches!(
+            record("1,4"),
+            Err(RecordError::MoveTooLong {
+                len: 4,
+                width: 3,
+                height: 3
+            })
+        ));
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut start = Board::with_players(11, 11, 2);
+        start.rules = "lengths=1,2,3 diagonal scorch=origin".parse().unwrap();
+        start.seed = Some(42);
+
+        let mut record = GameRecord::new(start);
+        record.players = vec![String::from("The \"Ace\""), String::from(r"C:\Yellow")];
+        record.date = Some(String::from("2023-02-18"));
+        record.tags.push((String::from("Event"), String::from(r#"Club "night" \o/"#)));
+        record.moves = "D2 U3 DR1 L1".split(' ').map(|m| m.parse().unwrap()).collect();
+
+        let text = record.to_string();
+        assert!(text.contains(r#"[Players "The \"Ace\", C:\\Yellow"]"#));
+        assert!(text.contains(r#"[Event "Club \"night\" \\o/"]"#));
+
+        let again: GameRecord = text.parse().unwrap();
+        assert_eq!(again.players, record.players);
+        assert_eq!(again.date, record.date);
+        assert_eq!(again.moves, record.moves);
+        assert_eq!(again.tags, record.tags);
+        assert!(again.start == record.start);
+        assert!(again.replay().unwrap() == record.replay().unwrap());
+        assert_eq!(again.to_string(), text);
+    }
+
+    #[test]
+    fn bad_tags() {
+        for tag in [
+            r#"[Event "unfinished]"#,
+            r#"[Event "a "quote" inside"]"#,
+            r#"[Event "trailing" junk]"#,
+            r#"[Event "escaped at the end\"]"#,
+            "[Event]",
+        ] {
+            assert!(
+                matches!(tag.parse::<GameRecord>(), Err(RecordError::InvalidTag(_))),
+                "{tag}"
+            );
+        }
+    }
+
+    #[test]
+    fn unknown_rules() {
+        assert!(matches!(
+            "lengths=1,2 teleport".parse::<Rules>(),
+            Err(RecordError::InvalidRules(word)) if word == "teleport"
+        ));
+        assert!(matches!(
+            "scorch=everywhere".parse::<Rules>(),
+            Err(RecordError::InvalidRules(_))
+        ));
+        assert!(matches!(
+            "[Rules \"lengths=1 diagnal\"]\n".parse::<GameRecord>(),
+            Err(RecordError::InvalidRules(word)) if word == "diagnal"
+        ));
+    }
+
+    #[test]
+    fn zero_length_moves() {
+        assert!(matches!("U0".parse::<Move>(), Err(RecordError::InvalidMove(_))));
+        assert!(matches!("DR00".parse::<Move>(), Err(RecordError::InvalidMove(_))));
+        assert!(matches!(
+            "[Players \"Green, Yellow\"]\n\nD1 U0 R1".parse::<GameRecord>(),
+            Err(RecordError::InvalidMove(text)) if text == "U0"
+        ));
+        assert_eq!("DR10".parse::<Move>().unwrap().len, 10);
+    }
+}
@@ -0,0 +1,262 @@
+use crate::{
+    Board, DrawReason, GameOutcome, LossReason, Move, MoveError, PlayerId, ScorchRule, ScorchState,
+    TileContents, TurnResult,
+};
+
+// A variant where everyone picks a move without seeing anyone else's, and they all happen at
+// once instead of taking turns. The moves are worked out like this:
+//
+// - Each move has to be legal in the position before anyone moves, the same as if it was that
+//   player's turn
+// - Everyone leaves their tile at the same time, scorching it (and their path with the path rule)
+// - Players that land on the same tile crash head-on and are all eliminated
+// - Players that land on a tile someone else scorched this turn are run over. That includes where
+//   someone else started, so two players swapping places both lose, but paths that only cross in
+//   the middle are fine.
+// - Then anyone left who is stuck loses as usual, except that if everyone is knocked out at once
+//   it's a draw
+// - With the territory rule, the game can then end if nobody can reach an opponent any more
+//
+// Whose turn it is doesn't mean anything here, so it only changes if that player is eliminated.
+
+impl Board {
+    // Make a move for every player still in the game at once, given in player order
+    pub fn resolve_simultaneous(&mut self, moves: &[Move]) -> Result<TurnResult, MoveError> {
+        if self.outcome != GameOutcome::Ongoing {
+            return Err(MoveError::GameOver);
+        }
+
+        let movers: Vec<PlayerId> = self.active_players().collect();
+        if moves.len() != movers.len() {
+            return Err(MoveError::WrongMoveCount {
+                expected: movers.len(),
+                found: moves.len(),
+            });
+        }
+
+        for (&player, &m) in movers.iter().zip(moves) {
+            self.check_move(player, m)?;
+        }
+
+        let mut changes = Vec::new();
+        let mut undo = self.undo_record(movers[0]);
+        undo.also_moved = movers[1..]
+            .iter()
+            .map(|&id| (id, self.players[id].pos))
+            .collect();
+
+        for (&player, &m) in movers.iter().zip(moves) {
+            let start = self.players[player].pos;
+            let scorched_len = match self.rules.scorch {
+                ScorchRule::Path => m.len,
+                ScorchRule::Origin => 1,
+            };
+
+            // check_move made sure the whole path is on the board
+            for i in 0..scorched_len as isize {
+                let Some(pos) = self.translate(start, m.dir.to_vector() * i) else { continue };
+                if self.scorch_state(pos) == Some(ScorchState::Empty) {
+                    self.set_scorch_state(pos, ScorchState::Scorched);
+                    changes.push((pos, TileContents::Scorched));
+                    undo.scorched.push(pos);
+                }
+            }
+
+            self.hash ^= self.player_key(player);
+            self.players[player].pos = self.translate(start, m.to_vector()).unwrap_or(start);
+            self.hash ^= self.player_key(player);
+        }
+        self.update_occupied();
+
+        for &player in &movers {
+            let player = &self.players[player];
+            changes.push((player.pos, TileContents::Player(player.color)));
+        }
+
+        // Crashes come first, so that anyone stuck only because of a crashed player's soldier
+        // is free again once it's gone
+        let collided: Vec<PlayerId> = movers
+            .iter()
+            .copied()
+            .filter(|&id| {
+                movers
+                    .iter()
+                    .any(|&other| other != id && self.players[other].pos == self.players[id].pos)
+            })
+            .collect();
+
+        let eliminated_len = self.eliminated.len();
+        self.eliminated.extend(collided.iter().map(|&id| (id, LossReason::Collided)));
+        self.update_occupied();
+
+        // Everyone else is checked at the same time, so the order doesn't matter
+        let stuck: Vec<(PlayerId, LossReason)> = movers
+            .iter()
+            .filter(|id| !collided.contains(id))
+            .filter_map(|&id| self.loss_reason(id).map(|reason| (id, reason)))
+            .collect();
+
+        let mut losers: Vec<(PlayerId, LossReason)> =
+            self.eliminated.drain(eliminated_len..).collect();
+        losers.extend(stuck);
+        self.update_occupied();
+
+        if losers.len() == movers.len() {
+            self.outcome = GameOutcome::Draw {
+                reason: DrawReason::EveryoneLost,
+            };
+            return Ok(TurnResult {
+                outcome: self.outcome,
+                eliminated: Vec::new(),
+                changes,
+                territory: None,
+                undo,
+            });
+        }
+
+        for &(id, reason) in &losers {
+            self.eliminate(id, reason, &mut changes);
+        }
+
+        let territory = self.score_territory(&mut losers, &mut changes);
+
+        if self.is_eliminated(self.turn) {
+            self.set_turn(self.next_active_player(self.turn));
+        }
+
+        Ok(TurnResult {
+            outcome: self.outcome,
+            eliminated: losers,
+            changes,
+            territory,
+            undo,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Direction, GameRecord, RecordError, Vector};
+
+    fn board(notation: &str) -> Board {
+        let mut board: Board = notation.parse().unwrap();
+        board.rules.simultaneous = true;
+        board
+    }
+
+    fn mv(dir: Direction, len: usize) -> Move {
+        Move { dir, len }
+    }
+
+    #[test]
+    fn one_move_at_a_time() {
+        let mut b = board("5x1 5. G@0.0,Y@4.0 G");
+        assert_eq!(
+            b.make_move(0, mv(Direction::Right, 1)).err(),
+            Some(MoveError::Simultaneous)
+        );
+        assert_eq!(b.players()[0].pos, Vector { x: 0, y: 0 });
+    }
+
+    #[test]
+    fn head_on_collision() {
+        // Both land in the middle, so nobody is left
+        let mut b = board("5x1 5. G@0.0,Y@4.0 G");
+        let res = b.resolve_simultaneous(&[mv(Direction::Right, 2), mv(Direction::Left, 2)]);
+        assert_eq!(
+            res.unwrap().outcome,
+            GameOutcome::Draw {
+                reason: DrawReason::EveryoneLost
+            }
+        );
+
+        // With a third player, they're the only one left
+        let mut b = board("5x3 5./5./5. G@0.0,Y@4.0,B@2.2 G");
+        let res = b
+            .resolve_simultaneous(&[
+                mv(Direction::Right, 2),
+                mv(Direction::Left, 2),
+                mv(Direction::Left, 1),
+            ])
+            .unwrap();
+        assert_eq!(
+            res.eliminated,
+            vec![(0, LossReason::Collided), (1, LossReason::Collided)]
+        );
+        assert!(matches!(res.outcome, GameOutcome::Win { winner: 2, .. }));
+    }
+
+    #[test]
+    fn swapping_places() {
+        // Each lands on the tile the other just scorched by leaving it
+        let mut b = board("4x3 4./4./4. G@1.0,Y@2.0,B@0.2 G");
+        let res = b
+            .resolve_simultaneous(&[
+                mv(Direction::Right, 1),
+                mv(Direction::Left, 1),
+                mv(Direction::Right, 1),
+            ])
+            .unwrap();
+        assert_eq!(
+            res.eliminated,
+            vec![(0, LossReason::RunOver), (1, LossReason::RunOver)]
+        );
+        assert!(matches!(res.outcome, GameOutcome::Win { winner: 2, .. }));
+    }
+
+    #[test]
+    fn paths_crossing_in_the_middle() {
+        // Both pass over 1.1, but neither lands anywhere the other scorched
+        let start = board("5x5 5./5./5./5./5. G@1.0,Y@0.1 G");
+        let mut b = start.clone();
+        let res = b
+            .resolve_simultaneous(&[mv(Direction::Down, 2), mv(Direction::Right, 2)])
+            .unwrap();
+        assert_eq!(res.outcome, GameOutcome::Ongoing);
+        assert!(res.eliminated.is_empty());
+        assert_eq!(b.players()[0].pos, Vector { x: 1, y: 2 });
+        assert_eq!(b.players()[1].pos, Vector { x: 2, y: 1 });
+        assert_eq!(b.scorched().count(), 3);
+
+        // Taking the round back puts both players back
+        b.unmake_move(res.undo);
+        assert!(b == start);
+    }
+
+    #[test]
+    fn wrong_number_of_moves() {
+        let mut b = board("5x5 5./5./5./5./5. G@1.0,Y@0.1 G");
+        assert_eq!(
+            b.resolve_simultaneous(&[mv(Direction::Down, 2)]).err(),
+            Some(MoveError::WrongMoveCount {
+                expected: 2,
+                found: 1
+            })
+        );
+    }
+
+    #[test]
+    fn records_replay_a_round_at_a_time() {
+        let text = "[Rules \"lengths=1,2 simultaneous scorch=path\"]\n\
+                    [Start \"5x5 5./5./5./5./5. G@1.0,Y@0.1 G\"]\n\nD2 R2 D1 D1\n";
+        let record: GameRecord = text.parse().unwrap();
+        assert!(record.start.rules.simultaneous);
+        assert!(record.to_string().contains("simultaneous"));
+
+        let b = record.replay().unwrap();
+        assert_eq!(b.players()[0].pos, Vector { x: 1, y: 3 });
+        assert_eq!(b.players()[1].pos, Vector { x: 2, y: 2 });
+
+        // A round that's missing a move can't be played
+        let mut short = record.clone();
+        short.moves.pop();
+        assert!(matches!(
+            short.replay(),
+            Err(RecordError::IllegalMove {
+                number: 3,
+                error: MoveError::WrongMoveCount { .. }
+            })
+        ));
+    }
+}
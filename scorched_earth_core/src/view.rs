@@ -0,0 +1,137 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{Bitboard, Board, GameOutcome, PlayerId, TileContents, Vector};
+
+// What one player can see of the board when playing with fog of war, so that online games only
+// need to send each player their own view
+#[derive(Clone, Serialize, Deserialize, PartialEq)]
+pub struct PlayerView {
+    pub player: PlayerId,
+    // The tiles the player can see
+    pub visible: Bitboard,
+    // The board with everything out of sight taken off. Tiles out of sight are empty (apart from
+    // walls, since everyone has the map), and players out of sight are all put on the same tile
+    // somewhere out of sight. That tile doesn't count as taken, so the player's own moves are
+    // judged the same as on the whole board.
+    pub board: Board,
+}
+
+impl PlayerView {
+    pub fn can_see(&self, pos: Vector) -> bool {
+        self.board
+            .layout
+            .index(pos)
+            .is_some_and(|index| self.visible.get(index))
+    }
+
+    // What the player knows is on a tile, or None if it's out of sight or off the board. Walls
+    // are always known.
+    pub fn tile_contents_at(&self, pos: Vector) -> Option<TileContents> {
+        let contents = self.board.tile_contents_at(pos)?;
+        (self.can_see(pos) || matches!(contents, TileContents::Wall)).then_some(contents)
+    }
+}
+
+impl Board {
+    // The tiles a player can see, which is everything within the fog radius of them or a teammate
+    // that's still in the game. It's the whole board when there's no fog, once the game is over,
+    // or for a player who is out and has nobody left to see for them.
+    pub fn visible_to(&self, player: PlayerId) -> Bitboard {
+        let Some(radius) = self.rules.fog.filter(|_| self.outcome == GameOutcome::Ongoing) else {
+            return self.layout.all.clone();
+        };
+
+        let mut visible = Bitboard::EMPTY;
+        for id in self
+            .active_players()
+            .filter(|&id| id == player || self.are_teammates(id, player))
+        {
+            if let Some(index) = self.layout.index(self.players[id].pos) {
+                visible.set(index);
+            }
+        }
+
+        if visible.is_empty() {
+            return self.layout.all.clone();
+        }
+
+        // Players can see in every direction, diagonals included, so they see a square around
+        // them (or a hexagon on a hex board)
+        for _ in 0..radius {
+            let mut next = visible.clone();
+            for dir in self.topology().directions(true) {
+                next |= self.layout.step(&visible, dir.to_vector());
+            }
+            visible = next;
+        }
+
+        visible
+    }
+
+    // The board as a player sees it
+    pub fn view_for(&self, player: PlayerId) -> PlayerView {
+        let visible = self.visible_to(player);
+
+        let mut board = self.clone();
+        board.scorched &= &visible;
+
+        let out_of_sight = self.layout.all.and_not(&visible).and_not(&self.walls);
+        if let Some(hidden) = out_of_sight.iter().next() {
+            let hiding_place = self.layout.position(hidden);
+            for (id, other) in board.players.iter_mut().enumerate() {
+                if !self.layout.index(other.pos).is_some_and(|index| visible.get(index)) {
+                    other.pos = hiding_place;
+                    if !self.is_eliminated(id) {
+                        board.hidden.push(id);
+                    }
+                }
+            }
+        }
+
+        board.update_occupied();
+        board.hash = board.compute_hash();
+
+        PlayerView {
+            player,
+            visible,
+            board,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Direction, Move, Rules, Topology};
+
+    #[test]
+    fn moves_onto_the_hiding_place() {
+        let mut board = Board::with_topology(7, 7, 2, Topology::Square);
+        board.rules = Rules {
+            move_lengths: vec![1, 2],
+            fog: Some(1),
+            ..Default::default()
+        };
+
+        // Yellow is out of sight, so goes on the first tile Green can't see, which is two to the
+        // right of Green
+        let mut view = board.view_for(0);
+        assert_eq!(view.board.players()[1].pos, Vector { x: 2, y: 0 });
+        assert!(!view.board.is_occupied(Vector { x: 2, y: 0 }));
+        assert!(view.board.is_consistent());
+
+        let m = Move {
+            dir: Direction::Right,
+            len: 2,
+        };
+        let on_view = view.board.make_move(0, m).unwrap();
+        let on_board = board.make_move(0, m).unwrap();
+        assert_eq!(on_view.eliminated, on_board.eliminated);
+        assert_eq!(on_view.outcome, on_board.outcome);
+        assert!(on_view.eliminated.is_empty());
+        assert!(matches!(
+            view.board.tile_contents_at(Vector { x: 2, y: 0 }),
+            Some(TileContents::Player(color)) if color == board.players()[0].color
+        ));
+    }
+}
@@ -0,0 +1,43 @@
+use crate::PlayerId;
+
+// Random-looking keys for Zobrist hashing. A position's hash is all the keys for the things in
+// it XORed together, so making or taking back a move only has to XOR in the keys that changed.
+// The keys are worked out from their index instead of stored, so every board (and every peer
+// over the network) agrees on them without a table.
+
+const SCORCHED: u64 = 1;
+const PLAYER: u64 = 2;
+const TURN: u64 = 3;
+const WALL: u64 = 4;
+
+// The splitmix64 finalizer, which scrambles consecutive inputs into unrelated outputs
+pub(crate) const fn mix(mut z: u64) -> u64 {
+    z = z.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+fn key(kind: u64, a: usize, b: usize) -> u64 {
+    mix((kind << 56) ^ ((a as u64) << 32) ^ b as u64)
+}
+
+// A scorched tile at a bit index
+pub fn scorched(index: usize) -> u64 {
+    key(SCORCHED, 0, index)
+}
+
+// A wall at a bit index
+pub fn wall(index: usize) -> u64 {
+    key(WALL, 0, index)
+}
+
+// A player still in the game standing at a bit index
+pub fn player(id: PlayerId, index: usize) -> u64 {
+    key(PLAYER, id, index)
+}
+
+// Whose turn it is
+pub fn turn(id: PlayerId) -> u64 {
+    key(TURN, 0, id)
+}
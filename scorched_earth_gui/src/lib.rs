@@ -6,7 +6,7 @@ use std::{
 #[cfg(target_os = "android")]
 use android_activity::{AndroidApp, WindowManagerFlags};
 use eframe::{egui::{self, RichText}, epaint::{Color32, Vec2}};
-use scorched_earth_core::{Board, Move, PlayerColor};
+use scorched_earth_core::{Board, Move, MoveError, PlayerColor, Topology};
 use scorched_earth_network::{Connection, MoveMessage};
 mod screens;
 
@@ -74,11 +74,14 @@ pub enum Screen {
         preview_move: Option<Move>,
         rx: Option<Receiver<Result<MoveMessage, scorched_earth_network::Error>>>,
         conn_player: usize,
+        move_error: Option<MoveError>,
     },
     Error(String),
     End {
-        won: bool,
-        color: PlayerColor,
+        // None if the game was a draw
+        won: Option<bool>,
+        color: Option<PlayerColor>,
+        reason: String,
     },
 }
 
@@ -91,6 +94,12 @@ impl Default for Screen {
 #[derive(Default)]
 pub struct State {
     screen: Screen,
+    // Path to a map file to host games on, or empty for the normal board
+    map_path: String,
+    // Whether to host on a randomly generated map when there's no map file
+    random_map: bool,
+    // The shape of the board for hosted games
+    topology: Topology,
 }
 
 impl State {
@@ -6,21 +6,27 @@ use eframe::{
 };
 
 pub fn render(screen: &mut Screen, ui: &mut egui::Ui) {
-    if let Screen::End { won, color } = screen {
+    if let Screen::End { won, color, reason } = screen {
         //ui.painter().rect_filled(ui.painter().clip_rect(), Rounding::none(), convert_color(*color));
         ui.add_space(100.0);
 
         ui.vertical_centered(|ui| {
             egui::Frame::none()
-                .fill(convert_color(*color))
+                .fill(color.map_or(Color32::GRAY, convert_color))
                 .show(ui, |ui| {
                     ui.heading(
-                        RichText::new(if *won { "You win!" } else { "You lost" })
+                        RichText::new(match won {
+                            Some(true) => "You win!",
+                            Some(false) => "You lost",
+                            None => "Draw",
+                        })
                         .color(Color32::WHITE)
                         .font(FontId::proportional(50.0))
                         .size(50.0),
                     );
                 });
+            ui.add_space(15.0);
+            ui.label(RichText::new(reason.as_str()).size(20.0).color(Color32::WHITE));
             // let (rect, _response) = ui.allocate_exact_size(egui::vec2(500.0, 500.0), Sense::focusable_noninteractive());
             // ui.painter().rect_filled(rect, Rounding::none(), convert_color(*color));
         });
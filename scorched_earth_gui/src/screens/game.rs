@@ -3,92 +3,161 @@ use std::{sync::mpsc::channel, thread};
 use crate::{convert_color, Screen, back_button};
 use eframe::{
     egui::{self, RichText},
-    epaint::{Color32, Rect, Rounding, Vec2},
+    epaint::{Color32, Rect, Rounding, Shape, Stroke, Vec2},
 };
-use scorched_earth_core::{Board, Direction, Move, PlayerColor, TileContents, Vector, BOARD_SIZE};
+use scorched_earth_core::{Board, Direction, GameOutcome, Move, TileContents, Topology, Vector};
 use scorched_earth_network::MoveMessage;
 
 fn draw_board(ui: &mut egui::Ui, board: &Board, preview_move: &Option<Move>, i: usize) {
+    // Hex boards are drawn with each row of hexagons shifted half a tile right of the row above,
+    // so they're a rhombus that's wider than the number of tiles across
+    let hex = board.topology() == Topology::Hex;
+    let columns = if hex {
+        board.width() as f32 + (board.height() - 1) as f32 / 2.0
+    } else {
+        board.width() as f32
+    };
+
     //let desired_size = ui.available_width() * 0.6 * egui::vec2(1.0, 1.0);
-    let width = (ui.available_width()) as usize / 11 * 11;
+    // Keep the tiles a whole number of pixels wide, and square even if the board isn't
+    let w = (ui.available_width() / columns).floor();
+    // Distance from the middle of a hexagon to its corners, when it's w wide
+    let radius = w / 3f32.sqrt();
     //let desired_size = egui::vec2(11f32 * 30f32, 11f32 * 30f32);
-    let desired_size = egui::vec2(width as f32, width as f32);
+    let desired_size = if hex {
+        egui::vec2(w * columns, radius * (1.5 * (board.height() - 1) as f32 + 2.0))
+    } else {
+        egui::vec2(w * board.width() as f32, w * board.height() as f32)
+    };
 
     let (rect, _response) =
         ui.allocate_exact_size(desired_size, egui::Sense::focusable_noninteractive());
 
+    let paint_tile = |pos: Vector, color: Color32| {
+        if hex {
+            let center = rect.left_top()
+                + egui::vec2(
+                    w * (pos.x as f32 + pos.y as f32 / 2.0 + 0.5),
+                    radius * (1.5 * pos.y as f32 + 1.0),
+                );
+            // Pointy-topped, so the rows fit together
+            let corners = (0..6)
+                .map(|k| {
+                    let angle = (30.0 + 60.0 * k as f32).to_radians();
+                    center + radius * egui::vec2(angle.cos(), angle.sin())
+                })
+                .collect();
+            ui.painter()
+                .add(Shape::convex_polygon(corners, color, Stroke::NONE));
+        } else {
+            let corner = rect.left_top() + egui::vec2(pos.x as f32 * w, pos.y as f32 * w);
+            ui.painter().rect_filled(
+                Rect {
+                    min: corner,
+                    max: corner + egui::vec2(w, w),
+                },
+                Rounding::none(),
+                color,
+            );
+        }
+    };
+
     if ui.is_rect_visible(rect) {
-        let w = rect.width() / BOARD_SIZE as f32;
-        for i in 0..BOARD_SIZE {
-            for j in 0..BOARD_SIZE {
-                if let Some(tile) = board.tile_contents_at(Vector {
+        for i in 0..board.width() {
+            for j in 0..board.height() {
+                let pos = Vector {
                     x: i as isize,
                     y: j as isize,
-                }) {
+                };
+                if let Some(tile) = board.tile_contents_at(pos) {
                     let color = match tile {
                         TileContents::Empty => Color32::BLACK,
                         TileContents::Scorched => Color32::RED,
+                        TileContents::Wall => Color32::DARK_GRAY,
                         TileContents::Player(p) => convert_color(p),
                     };
 
-                    let corner = rect.left_top() + egui::vec2(i as f32 * w, j as f32 * w);
-                    ui.painter().rect_filled(
-                        Rect {
-                            min: corner,
-                            max: corner + egui::vec2(w, w),
-                        },
-                        Rounding::none(),
-                        color,
-                    );
+                    paint_tile(pos, color);
                 }
             }
         }
 
         if let Some(potential_move) = preview_move {
-            let target_position = board.players[i].pos + potential_move.to_vector();
-            if (0..BOARD_SIZE as isize).contains(&target_position.x)
-                && (0..BOARD_SIZE as isize).contains(&target_position.y)
-            {
+            let start = board.players()[i].pos;
+            if board.translate(start, potential_move.to_vector()).is_some() {
                 let color = if board.is_move_valid(i, *potential_move) {
                     Color32::WHITE
                 } else {
                     Color32::GRAY
                 };
-                for tile in potential_move.tiles_along_path() {
-                    let pos = board.players[i].pos + tile;
-                    let corner = rect.left_top() + egui::vec2(pos.x as f32 * w, pos.y as f32 * w);
-                    ui.painter().rect_filled(
-                        Rect {
-                            min: corner,
-                            max: corner + egui::vec2(w, w),
-                        },
-                        Rounding::none(),
-                        color,
-                    );
+                // On a torus the path can go off one edge and come back on the other
+                for pos in potential_move
+                    .tiles_along_path()
+                    .into_iter()
+                    .filter_map(|tile| board.translate(start, tile))
+                {
+                    paint_tile(pos, color);
                 }
             }
         }
     }
 }
 
+// The screen to switch to if the game just ended. conn_player is the player on the other end of
+// the connection, so the local player won if anyone else did.
+fn game_over_screen(board: &Board, outcome: GameOutcome, conn_player: usize) -> Option<Screen> {
+    let (won, color) = match outcome {
+        GameOutcome::Ongoing => return None,
+        GameOutcome::Win { winner, .. } => {
+            let color = board.players().get(winner).map(|player| player.color);
+            (Some(winner != conn_player), color)
+        }
+        GameOutcome::TeamWin { team, .. } => {
+            (Some(!board.is_winner(conn_player)), board.team_color(team))
+        }
+        GameOutcome::Draw { .. } => (None, None),
+    };
+
+    Some(Screen::End {
+        won,
+        color,
+        reason: board.outcome_summary(),
+    })
+}
+
 pub fn render(screen: &mut Screen, ui: &mut egui::Ui) {
     back_button(ui, screen);
     ui.add_space(15.0);
     let mut error_message: Option<String> = None;
-    let mut won: Option<(bool, PlayerColor)> = None;
+    let mut end_screen: Option<Screen> = None;
     if let Screen::Game {
         conn,
         board,
         preview_move,
         rx,
         conn_player,
+        move_error,
     } = screen
     {
-        let i = board.turn;
+        let i = board.turn();
         let conn_player = *conn_player;
         ui.vertical_centered(|ui| {
             draw_board(ui, board, preview_move, i);
             ui.add_space(15.0);
+            if let Some(seed) = board.seed {
+                ui.label(
+                    RichText::new(format!("map seed {}", seed))
+                        .size(17.0)
+                        .color(Color32::WHITE),
+                );
+            }
+            if let Some(e) = move_error {
+                ui.label(
+                    RichText::new(format!("can't move there: {}", e))
+                        .size(17.0)
+                        .color(Color32::RED),
+                );
+            }
         });
 
         // it's the online player's turn
@@ -106,17 +175,21 @@ pub fn render(screen: &mut Screen, ui: &mut egui::Ui) {
             if let Some(r) = rx {
                 if let Ok(res) = r.try_recv() {
                     match res {
-                        Ok(m) => {
-                            let res = board.make_move(i, m.new_move);
-                            if board != &m.new_board {
-                                error_message =
-                                    Some(String::from("Other player's board doesn't match!"));
+                        Ok(m) => match board.make_move(m.player, m.new_move) {
+                            Ok(res) => {
+                                end_screen = game_over_screen(board, res.outcome, conn_player);
+                                // If the other player has a board that doesn't match, it's
+                                // probably cheating or something
+                                if m.hash.is_some_and(|hash| hash != board.hash()) {
+                                    error_message =
+                                        Some("Other player's board doesn't match!".to_string());
+                                }
                             }
-                            if let Some(color) = res.winner {
-                                let lost = color == board.players[conn_player].color;
-                                won = Some((!lost, color));
+                            Err(e) => {
+                                error_message =
+                                    Some(format!("Other player made an illegal move: {}", e));
                             }
-                        }
+                        },
                         Err(e) => {
                             error_message = Some(e.to_string());
                         }
@@ -142,6 +215,23 @@ pub fn render(screen: &mut Screen, ui: &mut egui::Ui) {
             let done_button =
                 egui::widgets::Button::new(RichText::new("done").size(20.0).color(Color32::WHITE));
 
+            // Diagonal buttons go in the corners of the grid, but only if the rules allow them (or
+            // it's a hex board, where they go to the diagonal neighbours)
+            let topology = board.topology();
+            let directions = board.directions();
+            let diagonal_button = |label: &str, dir: Direction, ui: &mut egui::Ui| {
+                let button = egui::widgets::Button::new(
+                    RichText::new(label).size(20.0).color(Color32::WHITE),
+                );
+                if directions.contains(&topology.from_screen(dir))
+                    && ui.add_sized(ui.available_size(), button).clicked()
+                {
+                    Some(dir)
+                } else {
+                    None
+                }
+            };
+
             let row_length = ui.available_width() * 0.95;
             ui.vertical_centered(|ui| {
                 ui.allocate_ui(
@@ -151,11 +241,21 @@ pub fn render(screen: &mut Screen, ui: &mut egui::Ui) {
                     },
                     |ui| {
                         ui.columns(3, |columns| {
+                            columns[0].vertical_centered(|ui| {
+                                if let Some(dir) = diagonal_button("↖", Direction::UpLeft, ui) {
+                                    input = Some(dir);
+                                }
+                            });
                             columns[1].vertical_centered(|ui| {
                                 if ui.add_sized(ui.available_size(), up_button).clicked() {
                                     input = Some(Direction::Up);
                                 }
-                            })
+                            });
+                            columns[2].vertical_centered(|ui| {
+                                if let Some(dir) = diagonal_button("↗", Direction::UpRight, ui) {
+                                    input = Some(dir);
+                                }
+                            });
                         });
                     },
                 );
@@ -175,22 +275,26 @@ pub fn render(screen: &mut Screen, ui: &mut egui::Ui) {
                             columns[1].vertical_centered(|ui| {
                                 if ui.add_sized(ui.available_size(), done_button).clicked() {
                                     if let Some(m) = preview_move {
-                                        if board.is_move_valid(i, *m) {
-                                            let res = board.make_move(i, *m);
-                                            if let Some(color) = res.winner {
-                                                let lost =
-                                                    color == board.players[conn_player].color;
-                                                won = Some((!lost, color));
-                                            }
-                                            match conn.lock().unwrap().send_move(MoveMessage {
-                                                new_board: board.clone(),
-                                                new_move: *m,
-                                                player: i,
-                                            }) {
-                                                Err(e) => error_message = Some(e.to_string()),
-                                                _ => {}
+                                        match board.make_move(i, *m) {
+                                            Ok(res) => {
+                                                *move_error = None;
+                                                end_screen = game_over_screen(
+                                                    board,
+                                                    res.outcome,
+                                                    conn_player,
+                                                );
+                                                if let Err(e) =
+                                                    conn.lock().unwrap().send_move(MoveMessage {
+                                                        new_move: *m,
+                                                        player: i,
+                                                        hash: Some(board.hash()),
+                                                    })
+                                                {
+                                                    error_message = Some(e.to_string());
+                                                }
+                                                *preview_move = None;
                                             }
-                                            *preview_move = None;
+                                            Err(e) => *move_error = Some(e),
                                         }
                                     }
                                 }
@@ -210,31 +314,28 @@ pub fn render(screen: &mut Screen, ui: &mut egui::Ui) {
                     },
                     |ui| {
                         ui.columns(3, |columns| {
+                            columns[0].vertical_centered(|ui| {
+                                if let Some(dir) = diagonal_button("↙", Direction::DownLeft, ui) {
+                                    input = Some(dir);
+                                }
+                            });
                             columns[1].vertical_centered(|ui| {
                                 if ui.add_sized(ui.available_size(), down_button).clicked() {
                                     input = Some(Direction::Down);
                                 }
-                            })
+                            });
+                            columns[2].vertical_centered(|ui| {
+                                if let Some(dir) = diagonal_button("↘", Direction::DownRight, ui) {
+                                    input = Some(dir);
+                                }
+                            });
                         });
                     },
                 );
             });
-            if let Some(dir) = input {
-                match preview_move {
-                    None => {
-                        *preview_move = Some(Move { dir, len: 1 });
-                    }
-                    Some(old) => {
-                        if dir == old.dir && old.len == 1 {
-                            old.len = 2;
-                        } else if dir == old.dir.opposite() && old.len == 2 {
-                            old.len = 1;
-                        } else {
-                            old.len = 1;
-                            old.dir = dir;
-                        }
-                    }
-                }
+            if let Some(dir) = input.map(|dir| topology.from_screen(dir)) {
+                *preview_move = Some(board.rules.preview_move(*preview_move, dir));
+                *move_error = None;
             }
         }
     }
@@ -242,7 +343,7 @@ pub fn render(screen: &mut Screen, ui: &mut egui::Ui) {
         *screen = Screen::Error(e);
     }
 
-    if let Some((won, color)) = won {
-        *screen = Screen::End { won, color };
+    if let Some(end_screen) = end_screen {
+        *screen = end_screen;
     }
 }
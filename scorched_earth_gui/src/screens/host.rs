@@ -25,6 +25,14 @@ pub fn render(screen: &mut Screen, ui: &mut egui::Ui) {
                     .font(FontId::proportional(150.0))
                     .size(100.0),
             );
+            if let Some(seed) = board.seed {
+                ui.add_space(50.0);
+                ui.label(
+                    RichText::new(format!("map seed {}", seed))
+                        .size(20.0)
+                        .color(Color32::WHITE),
+                );
+            }
         });
         if let Ok(res) = rx.try_recv() {
             match res {
@@ -36,6 +44,7 @@ pub fn render(screen: &mut Screen, ui: &mut egui::Ui) {
                         preview_move: None,
                         rx: None,
                         conn_player,
+                        move_error: None,
                     };
                 }
                 Err(e) => {
@@ -12,6 +12,16 @@ pub fn render(screen: &mut Screen, ui: &mut egui::Ui) {
     if let Screen::Join(rx) = screen {
         if let Ok(res) = rx.try_recv() {
             match res {
+                // Games with more players, fog of war or simultaneous moves need the TUI
+                Ok((_, board))
+                    if board.players().len() != 2
+                        || board.rules.fog.is_some()
+                        || board.rules.simultaneous =>
+                {
+                    *screen = Screen::Error(
+                        "this game needs the terminal version to play".to_string(),
+                    );
+                }
                 Ok((conn, board)) => {
                     let conn_player = conn.player_num;
                     *screen = Screen::Game {
@@ -20,6 +30,7 @@ pub fn render(screen: &mut Screen, ui: &mut egui::Ui) {
                         preview_move: None,
                         rx: None,
                         conn_player,
+                        move_error: None,
                     };
                 }
                 Err(e) => {
@@ -1,15 +1,40 @@
 use std::{sync::mpsc::channel, thread};
 
 use rand::{thread_rng, Rng, distributions::Uniform};
-use scorched_earth_core::Board;
+use scorched_earth_core::{Board, GenerateOptions, Topology, DEFAULT_BOARD_SIZE};
 use scorched_earth_network::Connection;
 
 use crate::{Screen, State};
-use eframe::{egui::{self, RichText, FontId}, epaint::{Color32, Vec2}};
+use eframe::{egui::{self, RichText, FontId, TextEdit}, epaint::{Color32, Vec2}};
 
 const ADDR: &str = "169.231.11.248:8080";
 const SECRET_LEN: usize = 6;
 
+// The board to host a game on, from a map file if there is one, otherwise a random map if asked
+// for, otherwise the normal board
+fn load_map(path: &str, random: bool, topology: Topology) -> Result<Board, String> {
+    if path.trim().is_empty() {
+        if random {
+            let options = GenerateOptions {
+                topology,
+                ..Default::default()
+            };
+            return Board::generate(thread_rng().gen(), &options).map_err(|e| e.to_string());
+        }
+        return Ok(Board::with_topology(DEFAULT_BOARD_SIZE, DEFAULT_BOARD_SIZE, 2, topology));
+    }
+
+    let map = std::fs::read_to_string(path.trim()).map_err(|e| e.to_string())?;
+    let mut board = Board::from_map(&map).map_err(|e| e.to_string())?;
+    board.set_topology(topology);
+
+    // The network only supports two players
+    if board.players().len() != 2 {
+        return Err(String::from("Hosted games need a map for 2 players"));
+    }
+    Ok(board)
+}
+
 pub fn render(state: &mut State, ui: &mut egui::Ui) {
     let host_button = egui::widgets::Button::new(RichText::new("Host Game")
         .size(30.0)
@@ -44,10 +69,34 @@ pub fn render(state: &mut State, ui: &mut egui::Ui) {
 
         ui.add_space(50.0);
 
+        ui.add(TextEdit::singleline(&mut state.map_path)
+            .hint_text("map file (optional)")
+            .font(FontId::proportional(20.0))
+            .desired_width(300.0));
+
+        ui.checkbox(&mut state.random_map, RichText::new("random map").size(20.0).color(Color32::WHITE));
+        ui.horizontal(|ui| {
+            for (topology, label) in [
+                (Topology::Square, "square"),
+                (Topology::Torus, "wrap around edges"),
+                (Topology::Hex, "hex tiles"),
+            ] {
+                ui.radio_value(&mut state.topology, topology, RichText::new(label).size(20.0).color(Color32::WHITE));
+            }
+        });
+
+        ui.add_space(10.0);
+
         if ui.add(host_button).clicked() {
-            let mut board = Board::default();
+            let mut board = match load_map(&state.map_path, state.random_map, state.topology) {
+                Ok(board) => board,
+                Err(e) => {
+                    state.screen = Screen::Error(e);
+                    return;
+                }
+            };
             let mut rng = thread_rng();
-            board.turn = if rng.gen_bool(0.5) { 1 } else { 0 };
+            board.set_turn(if rng.gen_bool(0.5) { 1 } else { 0 });
             let mut secret = [0u8; SECRET_LEN];
             for b in &mut secret {
                 *b = rng.sample(Uniform::new_inclusive(b'0', b'9'));
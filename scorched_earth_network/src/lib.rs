@@ -1,30 +1,111 @@
 use std::{
     io::{self, Read, Write},
     net::{TcpStream, ToSocketAddrs},
+    thread,
 };
 
 use rmp_serde::{to_vec, from_slice};
-use scorched_earth_core::{Board, Move};
-use serde::{Serialize, Deserialize};
+use scorched_earth_core::{Board, Move, PlayerId, PlayerView};
+use serde::{de::DeserializeOwned, Serialize, Deserialize};
 use serp::SerpError;
 use sha2::Digest;
 use snow::{Builder, TransportState};
 use thiserror::Error;
 
-static PARAMS: &'static str = "Noise_XXpsk3_25519_ChaChaPoly_BLAKE2s";
+static PARAMS: &str = "Noise_XXpsk3_25519_ChaChaPoly_BLAKE2s";
 
 pub struct Connection {
     noise: TransportState,
     stream: TcpStream,
     buf: Vec<u8>,
+    // The player on the other end of the connection
     pub player_num: usize,
 }
 
+// A game between any number of players, connected in a star: the host has a Connection to every
+// other player, and the others only talk to the host.
+//
+// The host keeps the real board. Everyone else sends their moves to the host, and after every
+// move the host sends each of them an UpdateMessage with only what they can see, so with fog of
+// war nobody else ever has the whole board. Each update commits to the whole board, and at the
+// end of the game the host sends every move so the others can check it didn't cheat.
+//
+// Two player games without fog of war or simultaneous moves skip all that, and both sides just
+// send each other their moves with MoveMessage like they would over a plain Connection, so they
+// can still play against the GUI.
+//
+// The host is always the last player. Everyone else joins with the same id and their own seat
+// number from 1 up, and plays as the player one less than their seat.
+pub struct Session {
+    // The host has one per other player, everyone else only has one to the host
+    peers: Vec<Connection>,
+    pub local_player: PlayerId,
+    host: bool,
+    // Whether moves go straight to the other player instead of through the host's updates
+    direct: bool,
+    // The host's moves and nonces so far, to send at the end of the game
+    moves: Vec<Move>,
+    nonces: Vec<[u8; 32]>,
+    // Everyone else's updates so far, and the moves they sent the host along with how many
+    // updates there had been before each one, to check at the end of the game
+    updates: Vec<UpdateMessage>,
+    sent: Vec<(usize, Move)>,
+}
+
+// A move sent to the host, or to the other player in two player games without fog of war
 #[derive(Serialize, Deserialize)]
 pub struct MoveMessage {
-    pub new_board: Board,
     pub new_move: Move,
     pub player: usize,
+    // The hash of the sender's board after the move, so the other side can check their boards
+    // still match. Left out by players who don't have the whole board.
+    pub hash: Option<u64>,
+}
+
+// What the host sends everyone else after each move
+#[derive(Clone, Serialize, Deserialize)]
+pub struct UpdateMessage {
+    pub player: PlayerId,
+    // Left out with fog of war, since the player might not have been able to see it
+    pub new_move: Option<Move>,
+    // What the receiving player can see after the move
+    pub view: PlayerView,
+    // A hash of the whole board after the move and a random nonce, so the host can't change
+    // anything out of sight later without it being noticed
+    pub commitment: [u8; 32],
+}
+
+// Sent by the host at the end of the game, so everyone can replay it and check every update
+#[derive(Serialize, Deserialize)]
+pub struct HistoryMessage {
+    pub moves: Vec<Move>,
+    pub nonces: Vec<[u8; 32]>,
+}
+
+// For simultaneous moves, everyone first sends a hash of their move, so they can't change it once
+// they've seen anyone else's but nobody can tell what it is yet...
+#[derive(Serialize, Deserialize)]
+pub struct CommitMessage {
+    pub player: PlayerId,
+    pub commitment: [u8; 32],
+}
+
+// ...and then once everyone has committed, the move itself along with the random nonce that went
+// into the hash, so nobody can work out the move by trying every possible one
+#[derive(Serialize, Deserialize)]
+pub struct RevealMessage {
+    pub player: PlayerId,
+    pub new_move: Move,
+    pub nonce: [u8; 32],
+}
+
+impl RevealMessage {
+    fn commitment(&self) -> Result<[u8; 32], Error> {
+        let mut hasher = sha2::Sha256::new();
+        hasher.update(self.nonce);
+        hasher.update(to_vec(&(self.player, self.new_move))?);
+        Ok(hasher.finalize().into())
+    }
 }
 
 #[derive(Error, Debug)]
@@ -43,6 +124,30 @@ pub enum Error {
     HashError(#[from] argon2::Error),
     #[error("Opponent disconnected ({0})")]
     DisconnectError(io::Error),
+    #[error("Host sent an invalid board")]
+    InvalidBoard,
+    #[error("Seat {0} isn't in this game")]
+    InvalidSeat(usize),
+    #[error("Not connected to player {0}")]
+    UnknownPeer(PlayerId),
+    #[error("No move from player {0}")]
+    MissingMove(PlayerId),
+    #[error("Got a move from player {0} that wasn't expected")]
+    UnexpectedMove(PlayerId),
+    #[error("Player {0}'s move doesn't match the one they committed to")]
+    BrokenCommitment(PlayerId),
+    #[error("Update {0} from the host doesn't match the moves it sent at the end")]
+    BrokenHistory(usize),
+}
+
+// The secret for the room for a seat. The first seat uses the game id as it is, so two player
+// games work the same as before there were seats.
+fn seat_secret(secret: &[u8], seat: usize) -> Vec<u8> {
+    let mut seat_secret = secret.to_vec();
+    if seat > 1 {
+        seat_secret.extend(format!("-{}", seat).as_bytes());
+    }
+    seat_secret
 }
 
 impl Connection {
@@ -57,8 +162,18 @@ impl Connection {
     }
 
     pub fn host<A: ToSocketAddrs>(addr: A, secret: &[u8], board: &Board) -> Result<Self, Error> {
+        Self::host_seat(addr, secret, 1, board)
+    }
+
+    // Wait for the player in a seat to join and send them the board
+    pub fn host_seat<A: ToSocketAddrs>(
+        addr: A,
+        secret: &[u8],
+        seat: usize,
+        board: &Board,
+    ) -> Result<Self, Error> {
         let mut hasher = sha2::Sha256::new();
-        hasher.update(secret);
+        hasher.update(seat_secret(secret, seat));
         let secret = &hasher.finalize()[..32];
 
         let mut buf = vec![0u8; 65535];
@@ -91,8 +206,10 @@ impl Connection {
         // handshake complete
         let noise = noise.into_transport_mode()?;
 
-        let mut conn = Self { noise, stream, buf, player_num: 0 };
+        let mut conn = Self { noise, stream, buf, player_num: seat - 1 };
 
+        // The board carries its own dimensions, rules and map (walls and starting positions), so
+        // the joining player plays the same game
         let board_buf = to_vec(board)?;
 
         conn.send(&board_buf)?;
@@ -101,8 +218,17 @@ impl Connection {
     }
 
     pub fn conn<A: ToSocketAddrs>(addr: A, secret: &[u8]) -> Result<(Self, Board), Error> {
+        Self::conn_seat(addr, secret, 1)
+    }
+
+    // Join a game in a seat, getting the board from the host
+    pub fn conn_seat<A: ToSocketAddrs>(
+        addr: A,
+        secret: &[u8],
+        seat: usize,
+    ) -> Result<(Self, Board), Error> {
         let mut hasher = sha2::Sha256::new();
-        hasher.update(secret);
+        hasher.update(seat_secret(secret, seat));
         let secret = &hasher.finalize()[..32];
 
         let mut buf = vec![0u8; 65535];
@@ -133,11 +259,20 @@ impl Connection {
 
         let noise = noise.into_transport_mode()?;
 
+        // The host is the last player, which isn't known until the board arrives
         let mut conn = Self { noise, stream, buf, player_num: 1 };
 
         let board_buf = conn.recv()?;
         let board: Board = from_slice(board_buf)?;
 
+        if !board.is_consistent() {
+            return Err(Error::InvalidBoard);
+        }
+        if seat == 0 || seat >= board.players().len() {
+            return Err(Error::InvalidSeat(seat));
+        }
+        conn.player_num = board.players().len() - 1;
+
         Ok((conn, board))
     }
 
@@ -154,6 +289,20 @@ impl Connection {
     }
 }
 
+// Whether a game can be played with moves going straight between the players, the same way the
+// GUI plays
+fn is_direct(board: &Board) -> bool {
+    board.players().len() == 2 && board.rules.fog.is_none() && !board.rules.simultaneous
+}
+
+// What an update commits the host to
+fn board_commitment(board: &Board, nonce: &[u8; 32]) -> Result<[u8; 32], Error> {
+    let mut hasher = sha2::Sha256::new();
+    hasher.update(nonce);
+    hasher.update(to_vec(board)?);
+    Ok(hasher.finalize().into())
+}
+
 /// Receive some data preceded by 16-bit BE length
 fn tcp_recv(stream: &mut TcpStream) -> io::Result<Vec<u8>> {
     let mut msg_len_buf = [0u8; 2];
@@ -171,3 +320,273 @@ fn tcp_send(stream: &mut TcpStream, buf: &[u8]) -> io::Result<()> {
     stream.write_all(buf)?;
     Ok(())
 }
+
+impl Session {
+    // Host a game, waiting until every other player has joined
+    pub fn host<A: ToSocketAddrs + Sync>(
+        addr: A,
+        secret: &[u8],
+        board: &Board,
+    ) -> Result<Self, Error> {
+        let players = board.players().len();
+        let addr = &addr;
+
+        // Every seat's room has to be open at once, since players can join in any order
+        let peers = thread::scope(|s| {
+            let handles: Vec<_> = (1..players)
+                .map(|seat| s.spawn(move || Connection::host_seat(addr, secret, seat, board)))
+                .collect();
+
+            handles
+                .into_iter()
+                .map(|handle| handle.join().expect("hosting thread panicked"))
+                .collect::<Result<Vec<_>, _>>()
+        })?;
+
+        Ok(Self {
+            peers,
+            local_player: players - 1,
+            host: true,
+            direct: is_direct(board),
+            moves: Vec::new(),
+            nonces: Vec::new(),
+            updates: Vec::new(),
+            sent: Vec::new(),
+        })
+    }
+
+    // Join a game in a seat, getting the board from the host
+    pub fn join<A: ToSocketAddrs>(
+        addr: A,
+        secret: &[u8],
+        seat: usize,
+    ) -> Result<(Self, Board), Error> {
+        let (conn, board) = Connection::conn_seat(addr, secret, seat)?;
+
+        let session = Self {
+            peers: vec![conn],
+            local_player: seat - 1,
+            host: false,
+            direct: is_direct(&board),
+            moves: Vec::new(),
+            nonces: Vec::new(),
+            updates: Vec::new(),
+            sent: Vec::new(),
+        };
+        Ok((session, board))
+    }
+
+    pub fn is_host(&self) -> bool {
+        self.host
+    }
+
+    // Whether both players send each other their moves, rather than going through the host's
+    // updates and history
+    pub fn is_direct(&self) -> bool {
+        self.direct
+    }
+
+    // Send a move made here to the host, which sends back an update once it's made it. Only for
+    // players other than the host, unless the session is direct.
+    pub fn send_move(&mut self, msg: &MoveMessage) -> Result<(), Error> {
+        self.sent.push((self.updates.len(), msg.new_move));
+        self.peers[0].send(&to_vec(msg)?)
+    }
+
+    // Wait for the move of another player, whose turn it is. Only for the host, which should make
+    // the move and then send everyone an update, unless the session is direct.
+    pub fn recv_move(&mut self, from: PlayerId) -> Result<MoveMessage, Error> {
+        self.peers
+            .iter_mut()
+            .find(|peer| peer.player_num == from)
+            .ok_or(Error::UnknownPeer(from))?
+            .recv_move()
+    }
+
+    // Tell everyone else about a move once the host has made it on the board. Only for the host.
+    pub fn send_update(
+        &mut self,
+        board: &Board,
+        player: PlayerId,
+        new_move: Move,
+    ) -> Result<(), Error> {
+        let nonce: [u8; 32] = rand::random();
+        let commitment = board_commitment(board, &nonce)?;
+        self.moves.push(new_move);
+        self.nonces.push(nonce);
+
+        for peer in &mut self.peers {
+            let update = UpdateMessage {
+                player,
+                new_move: board.rules.fog.is_none().then_some(new_move),
+                view: board.view_for(peer.player_num),
+                commitment,
+            };
+            peer.send(&to_vec(&update)?)?;
+        }
+        Ok(())
+    }
+
+    // Wait for the host to send an update after a move. Only for players other than the host.
+    pub fn recv_update(&mut self) -> Result<UpdateMessage, Error> {
+        let update: UpdateMessage = from_slice(self.peers[0].recv()?)?;
+        // The view replaces the board with fog of war, so check it as carefully as the board sent
+        // at the start
+        if !update.view.board.is_consistent() || update.view.player != self.local_player {
+            return Err(Error::InvalidBoard);
+        }
+        self.updates.push(update.clone());
+        Ok(update)
+    }
+
+    // Send everyone every move once the game is over. Only for the host.
+    pub fn send_history(&mut self) -> Result<(), Error> {
+        let history = to_vec(&HistoryMessage {
+            moves: self.moves.clone(),
+            nonces: self.nonces.clone(),
+        })?;
+        for peer in &mut self.peers {
+            peer.send(&history)?;
+        }
+        Ok(())
+    }
+
+    // Get every move from the host once the game is over, and check that replaying them from the
+    // start gives the same updates the host sent during the game. Only for players other than
+    // the host.
+    pub fn recv_history(&mut self, start: &Board) -> Result<Vec<Move>, Error> {
+        let history: HistoryMessage = from_slice(self.peers[0].recv()?)?;
+
+        if history.moves.len() != self.updates.len() || history.nonces.len() != self.updates.len()
+        {
+            return Err(Error::BrokenHistory(self.updates.len().min(history.moves.len())));
+        }
+
+        // The host can't have changed any of the moves made here either
+        if let Some(&(i, _)) = self.sent.iter().find(|&&(i, m)| history.moves.get(i) != Some(&m)) {
+            return Err(Error::BrokenHistory(i));
+        }
+
+        let mut board = start.clone();
+        for (i, update) in self.updates.iter().enumerate() {
+            let matches = board.make_move(update.player, history.moves[i]).is_ok()
+                && board_commitment(&board, &history.nonces[i])? == update.commitment
+                && board.view_for(self.local_player) == update.view;
+            if !matches {
+                return Err(Error::BrokenHistory(i));
+            }
+        }
+
+        Ok(history.moves)
+    }
+
+    // Swap moves with everyone else for simultaneous moves, so that nobody sees anyone else's
+    // move before picking their own. The players are everyone still in the game, and the local
+    // move is only needed if the local player is one of them. Gives back all of their moves in
+    // the same order, ready for Board::resolve_simultaneous.
+    pub fn exchange_moves(
+        &mut self,
+        players: &[PlayerId],
+        local_move: Option<Move>,
+    ) -> Result<Vec<Move>, Error> {
+        let playing = players.contains(&self.local_player);
+        let reveal = match local_move {
+            Some(new_move) if playing => Some(RevealMessage {
+                player: self.local_player,
+                new_move,
+                nonce: rand::random(),
+            }),
+            None if playing => return Err(Error::MissingMove(self.local_player)),
+            _ => None,
+        };
+        let others: Vec<PlayerId> = players
+            .iter()
+            .copied()
+            .filter(|&id| id != self.local_player)
+            .collect();
+
+        let commit = match &reveal {
+            Some(reveal) => Some(CommitMessage {
+                player: reveal.player,
+                commitment: reveal.commitment()?,
+            }),
+            None => None,
+        };
+        let commits: Vec<CommitMessage> = self.exchange(commit.as_ref(), &others)?;
+        let commit_players: Vec<PlayerId> = commits.iter().map(|c| c.player).collect();
+        check_senders(&commit_players, &others)?;
+
+        let reveals: Vec<RevealMessage> = self.exchange(reveal.as_ref(), &others)?;
+        let reveal_players: Vec<PlayerId> = reveals.iter().map(|r| r.player).collect();
+        check_senders(&reveal_players, &others)?;
+
+        players
+            .iter()
+            .map(|&id| {
+                let Some(reveal) = reveals.iter().chain(&reveal).find(|r| r.player == id) else {
+                    return Err(Error::MissingMove(id));
+                };
+                // Everyone except the local player has a commitment to check against
+                if let Some(commit) = commits.iter().find(|c| c.player == id) {
+                    if commit.commitment != reveal.commitment()? {
+                        return Err(Error::BrokenCommitment(id));
+                    }
+                }
+                Ok(reveal.new_move)
+            })
+            .collect()
+    }
+
+    // Send a message to everyone and get one back from each of the other players, with the host
+    // passing each one on to everyone else like with moves
+    fn exchange<T: Serialize + DeserializeOwned>(
+        &mut self,
+        local: Option<&T>,
+        senders: &[PlayerId],
+    ) -> Result<Vec<T>, Error> {
+        if let Some(local) = local {
+            let msg_buf = to_vec(local)?;
+            for peer in &mut self.peers {
+                peer.send(&msg_buf)?;
+            }
+        }
+
+        let mut received = Vec::new();
+
+        if self.peers.len() == 1 {
+            for _ in senders {
+                received.push(from_slice(self.peers[0].recv()?)?);
+            }
+            return Ok(received);
+        }
+
+        for sender in 0..self.peers.len() {
+            if !senders.contains(&self.peers[sender].player_num) {
+                continue;
+            }
+
+            let msg_buf = self.peers[sender].recv()?.to_vec();
+            for (i, peer) in self.peers.iter_mut().enumerate() {
+                if i != sender {
+                    peer.send(&msg_buf)?;
+                }
+            }
+            received.push(from_slice(&msg_buf)?);
+        }
+
+        Ok(received)
+    }
+}
+
+// Make sure one message came from each expected player, so nobody can send one for someone else
+fn check_senders(received: &[PlayerId], expected: &[PlayerId]) -> Result<(), Error> {
+    for (i, &player) in received.iter().enumerate() {
+        if !expected.contains(&player) || received[..i].contains(&player) {
+            return Err(Error::UnexpectedMove(player));
+        }
+    }
+    match expected.iter().find(|id| !received.contains(id)) {
+        Some(&missing) => Err(Error::MissingMove(missing)),
+        None => Ok(()),
+    }
+}
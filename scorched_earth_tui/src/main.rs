@@ -1,22 +1,35 @@
 use anyhow::{bail, Result};
-use clap::{command, Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
 use crossterm::{
-    cursor::{Hide, MoveDown, MoveLeft, MoveRight, MoveUp, RestorePosition, SavePosition, Show},
+    cursor::{Hide, MoveDown, MoveRight, MoveUp, RestorePosition, SavePosition, Show},
     event::{Event, KeyCode, KeyEvent},
     execute,
     style::{Color, SetBackgroundColor},
-    terminal::{disable_raw_mode, enable_raw_mode},
+    terminal::{disable_raw_mode, enable_raw_mode, Clear, ClearType},
 };
 use rand::{distributions::Uniform, thread_rng, Rng};
-use scorched_earth_network::{Connection, MoveMessage};
+use scorched_earth_bot::{Budget, MctsConfig};
+use scorched_earth_network::{MoveMessage, Session, UpdateMessage};
+
+mod replay;
+mod save;
 
 use std::{
     ffi::OsString,
     io::{stdout, Write},
-    sync::mpsc::channel, time::Duration,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicU16, Ordering},
+        mpsc::channel,
+    },
+    time::{Duration, SystemTime},
 };
 
-use scorched_earth_core::{Board, Direction, Move, PlayerColor, TileContents, Vector, BOARD_SIZE};
+use scorched_earth_core::{
+    Board, Direction, GameOutcome, GameRecord, GenerateOptions, Move, PlayerColor, PlayerView,
+    Rules, ScorchRule, Symmetry, TileContents, Topology, Undo, Vector, DEFAULT_BOARD_SIZE,
+    MAX_PLAYERS,
+};
 
 const SECRET_LEN: usize = 6;
 
@@ -28,6 +41,250 @@ struct Cli {
     command: Option<Commands>,
     #[arg(short, long)]
     relay: Option<OsString>,
+    /// Width of the board when playing offline or hosting
+    #[arg(long, default_value_t = DEFAULT_BOARD_SIZE as u16, value_parser = clap::value_parser!(u16).range(2..=32))]
+    width: u16,
+    /// Height of the board when playing offline or hosting
+    #[arg(long, default_value_t = DEFAULT_BOARD_SIZE as u16, value_parser = clap::value_parser!(u16).range(2..=32))]
+    height: u16,
+    /// Number of players in an offline or hosted game
+    #[arg(short, long, default_value_t = 2, value_parser = clap::value_parser!(u8).range(2..=MAX_PLAYERS as i64))]
+    players: u8,
+    /// Split the players into this many teams, taking turns (so with 4 players and 2 teams, the
+    /// first and third players are against the second and fourth)
+    #[arg(long, value_parser = clap::value_parser!(u8).range(2..=MAX_PLAYERS as i64))]
+    teams: Option<u8>,
+    /// Allowed move lengths
+    #[arg(long, value_delimiter = ',', default_value = "1,2", value_parser = clap::value_parser!(u16).range(1..))]
+    move_lengths: Vec<u16>,
+    /// Allow diagonal moves (with the y, u, b and n keys)
+    #[arg(long)]
+    diagonal: bool,
+    /// Allow moves to jump over scorched tiles and other players
+    #[arg(long)]
+    jumping: bool,
+    /// Make it a draw when a move leaves every player stuck, instead of a win for the mover
+    #[arg(long)]
+    blockade_draw: bool,
+    /// Don't allow capturing other players by moving onto them
+    #[arg(long)]
+    no_capture: bool,
+    /// Which tiles get scorched when moving
+    #[arg(long, value_enum, default_value_t = ScorchArg::Path)]
+    scorch: ScorchArg,
+    /// Fog of war: players can only see tiles this close to them (or to a teammate)
+    #[arg(long)]
+    fog: Option<u16>,
+    /// End the game once nobody can reach an opponent, with whoever has the most room winning
+    #[arg(long)]
+    territory: bool,
+    /// Everyone picks a move without seeing the others', and they all happen at once
+    #[arg(long)]
+    simultaneous: bool,
+    /// What happens at the edges of the board
+    #[arg(long, value_enum, default_value_t = TopologyArg::Square)]
+    topology: TopologyArg,
+    /// Play offline against the computer, which controls every player except the first
+    #[arg(long, value_enum)]
+    bot: Option<BotArg>,
+    /// How long the computer gets to think about each move, in milliseconds
+    #[arg(long, default_value_t = 500)]
+    bot_time: u64,
+    /// Make the computer search a fixed number of moves ahead instead of using a time limit
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    bot_depth: Option<u32>,
+    /// How many random games the Monte Carlo bot plays out for each move
+    #[arg(long, default_value_t = MctsConfig::default().playouts)]
+    bot_playouts: u32,
+    /// Write a record of the game's moves to this file when it ends
+    #[arg(long)]
+    record: Option<PathBuf>,
+    /// Where to save an offline game when quitting before it's over
+    #[arg(long, default_value = "scorched_earth.save")]
+    save: PathBuf,
+    /// Carry on with an offline game that was saved when quitting
+    #[arg(long)]
+    resume: Option<PathBuf>,
+    /// Play on a map from a file instead of an empty board (this sets the size and number of
+    /// players)
+    #[arg(long)]
+    map: Option<PathBuf>,
+    /// Play on a randomly generated map
+    #[arg(long)]
+    random_map: bool,
+    /// Generate the map from this seed, to play a map shown in an earlier game again
+    #[arg(long)]
+    seed: Option<u64>,
+    /// How generated maps are made fair (by default upside down for two players, otherwise
+    /// mirrored)
+    #[arg(long, value_enum)]
+    symmetry: Option<SymmetryArg>,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum ScorchArg {
+    /// The starting tile and every tile passed over
+    Path,
+    /// Only the starting tile
+    Origin,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum TopologyArg {
+    /// The edges can't be moved past
+    Square,
+    /// The edges wrap around to the other side
+    Torus,
+    /// Hexagonal tiles, with six neighbours each
+    Hex,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum SymmetryArg {
+    /// The map looks the same upside down
+    Rotational,
+    /// The map looks the same flipped left to right or top to bottom
+    Mirror,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum BotArg {
+    /// Alpha-beta search, looking as many moves ahead as the budget allows
+    AlphaBeta,
+    /// Monte Carlo tree search, which copes better with lots of players
+    Mcts,
+}
+
+// A computer opponent and how much thinking it's allowed to do
+#[derive(Debug, Clone, Copy)]
+struct Bot {
+    kind: BotArg,
+    budget: Budget,
+    playouts: u32,
+}
+
+impl Bot {
+    fn choose_move(&self, board: &Board, player: usize) -> Option<Move> {
+        match self.kind {
+            BotArg::AlphaBeta => scorched_earth_bot::choose_move(board, player, self.budget),
+            BotArg::Mcts => {
+                let config = MctsConfig {
+                    playouts: self.playouts,
+                    seed: thread_rng().gen(),
+                    ..Default::default()
+                };
+                scorched_earth_bot::choose_move_mcts(board, player, &config)
+            }
+        }
+    }
+}
+
+impl Cli {
+    // The board to start offline or hosted games on, from the map file, a generated map or the
+    // board options
+    fn start_board(&self, players: usize) -> Result<Board> {
+        let (width, height) = (self.width as usize, self.height as usize);
+
+        let seed = match self.seed {
+            Some(seed) => Some(seed),
+            None if self.random_map => Some(thread_rng().gen()),
+            None => None,
+        };
+
+        let topology = match self.topology {
+            TopologyArg::Square => Topology::Square,
+            TopologyArg::Torus => Topology::Torus,
+            TopologyArg::Hex => Topology::Hex,
+        };
+
+        // Maps from files have their own starts, but otherwise tiny boards don't have room for
+        // everyone
+        if self.map.is_none() && !Board::has_room_for(width, height, players, topology) {
+            bail!("A {}x{} board is too small for {} players", width, height, players);
+        }
+
+        let mut board = match (&self.map, seed) {
+            (Some(path), _) => {
+                let mut board = Board::from_map(&std::fs::read_to_string(path)?)?;
+                board.set_topology(topology);
+                board
+            }
+            (None, Some(seed)) => {
+                let options = GenerateOptions {
+                    width,
+                    height,
+                    players,
+                    topology,
+                    symmetry: match self.symmetry {
+                        Some(SymmetryArg::Rotational) => Symmetry::Rotational,
+                        Some(SymmetryArg::Mirror) => Symmetry::Mirror,
+                        None if players > 2 => Symmetry::Mirror,
+                        None => Symmetry::Rotational,
+                    },
+                    rules: self.rules(),
+                    ..Default::default()
+                };
+                Board::generate(seed, &options)?
+            }
+            (None, None) => Board::with_topology(width, height, players, topology),
+        };
+        board.rules = self.rules();
+
+        if board.rules.max_move_len() > board.width().max(board.height()) {
+            bail!("Moves can't be longer than the board");
+        }
+
+        if board.rules.fog.is_some_and(|radius| radius < board.rules.max_move_len()) {
+            bail!("Players need to be able to see at least as far as they can move");
+        }
+
+        if board.rules.simultaneous && board.rules.fog.is_some() {
+            bail!("Simultaneous moves can't be played with fog of war");
+        }
+
+        if board.rules.simultaneous && self.bot.is_some() {
+            bail!("The computer can't play simultaneous moves");
+        }
+
+        if let Some(teams) = self.teams {
+            if teams as usize > board.players().len() {
+                bail!("Can't split {} players into {} teams", board.players().len(), teams);
+            }
+            board.set_teams(teams as usize);
+        }
+
+        Ok(board)
+    }
+
+    fn bot(&self) -> Option<Bot> {
+        let budget = match self.bot_depth {
+            Some(depth) => Budget::Depth(depth),
+            None => Budget::Time(Duration::from_millis(self.bot_time)),
+        };
+
+        self.bot.map(|kind| Bot {
+            kind,
+            budget,
+            playouts: self.bot_playouts,
+        })
+    }
+
+    fn rules(&self) -> Rules {
+        Rules {
+            move_lengths: self.move_lengths.iter().map(|&len| len as usize).collect(),
+            diagonal_moves: self.diagonal,
+            jumping: self.jumping,
+            mutual_blockade_draw: self.blockade_draw,
+            capture: !self.no_capture,
+            scorch: match self.scorch {
+                ScorchArg::Path => ScorchRule::Path,
+                ScorchArg::Origin => ScorchRule::Origin,
+            },
+            fog: self.fog.map(|radius| radius as usize),
+            territory: self.territory,
+            simultaneous: self.simultaneous,
+        }
+    }
 }
 
 #[derive(Debug, Subcommand)]
@@ -36,6 +293,18 @@ enum Commands {
     #[command(arg_required_else_help = true)]
     Join {
         id: OsString,
+        /// Which seat to join in, for games with more than two players (from 1 up to one less
+        /// than the number of players)
+        #[arg(long, default_value_t = 1)]
+        seat: usize,
+    },
+    /// Step through a game recorded with --record
+    #[command(arg_required_else_help = true)]
+    Replay {
+        file: PathBuf,
+        /// Milliseconds between moves when playing automatically
+        #[arg(long, default_value_t = 500)]
+        speed: u64,
     },
 }
 
@@ -49,19 +318,30 @@ fn player_term_color(color: PlayerColor) -> Color {
     }
 }
 
+// Height of the board that was drawn, so the cursor can be moved below it when finishing
+static DRAWN_HEIGHT: AtomicU16 = AtomicU16::new(0);
+
+// Whether the board was drawn for hex tiles, with each row shifted half a tile (one character)
+// right of the row above
+static HEX_LAYOUT: AtomicBool = AtomicBool::new(false);
+
 // Initial setup for drawing moves
 fn setup_drawing(board: &Board) -> crossterm::Result<()> {
     // Disables typing to the terminal so keyboard input isn't visible
     enable_raw_mode()?;
-    print!("\n");
+    println!();
 
     // Hide the cursor
     execute!(stdout(), Hide,)?;
 
+    DRAWN_HEIGHT.store(board.height() as u16, Ordering::Relaxed);
+    HEX_LAYOUT.store(board.topology() == Topology::Hex, Ordering::Relaxed);
+
     // Draw the board with a grey border and blank inside
-    for i in 0..BOARD_SIZE + 2 {
-        for j in 0..BOARD_SIZE + 2 {
-            let color = if i == 0 || j == 0 || i == BOARD_SIZE + 1 || j == BOARD_SIZE + 1 {
+    for i in 0..board.height() + 2 {
+        print!("{}", " ".repeat(row_indent(i)));
+        for j in 0..board.width() + 2 {
+            let color = if i == 0 || j == 0 || i == board.height() + 1 || j == board.width() + 1 {
                 Color::DarkMagenta
             } else {
                 Color::Reset
@@ -77,11 +357,17 @@ fn setup_drawing(board: &Board) -> crossterm::Result<()> {
 
     // Go back to the top and SAVE THE POSITION OF THE TOP LEFT CORNER (important; this is used
     // for drawing later)
-    execute!(stdout(), MoveUp(BOARD_SIZE as u16 + 4), SavePosition)?;
+    execute!(stdout(), MoveUp(board.height() as u16 + 4), SavePosition)?;
 
-    // Draw the players
-    for player in &board.players {
-        draw_tile(player.pos, player_term_color(player.color))?;
+    // Draw the players, and anything already scorched if the game is being resumed
+    for y in 0..board.height() as isize {
+        for x in 0..board.width() as isize {
+            let pos = Vector { x, y };
+            match board.tile_contents_at(pos) {
+                Some(TileContents::Empty) | None => {}
+                Some(contents) => draw_tile_contents(pos, contents)?,
+            }
+        }
     }
 
     Ok(())
@@ -93,21 +379,36 @@ fn finish_drawing() -> crossterm::Result<()> {
         stdout(),
         Show,
         RestorePosition,
-        MoveDown(BOARD_SIZE as u16 + 10),
+        MoveDown(DRAWN_HEIGHT.load(Ordering::Relaxed) + 10),
     )?;
 
     disable_raw_mode()
 }
 
-// Draw a single cell on the board. 2 characters wide to be more square-shaped
-fn draw_tile(pos: Vector, color: Color) -> crossterm::Result<()> {
-    execute!(
-        stdout(),
-        RestorePosition,
-        MoveDown((pos.y + 1) as u16),
-        MoveRight(((pos.x + 1) * 2) as u16),
-        SetBackgroundColor(color)
-    )?;
+// How far a row of the drawing (counting the border) is shifted right, for hex boards
+fn row_indent(row: usize) -> usize {
+    if HEX_LAYOUT.load(Ordering::Relaxed) {
+        row
+    } else {
+        0
+    }
+}
+
+// Draw a single cell of the drawing, counting the border as row and column 0. 2 characters wide
+// to be more square-shaped
+fn draw_cell(row: usize, column: usize, color: Color) -> crossterm::Result<()> {
+    execute!(stdout(), RestorePosition)?;
+
+    // Moving by 0 still moves by 1 in most terminals
+    if row > 0 {
+        execute!(stdout(), MoveDown(row as u16))?;
+    }
+    let right = column * 2 + row_indent(row);
+    if right > 0 {
+        execute!(stdout(), MoveRight(right as u16))?;
+    }
+
+    execute!(stdout(), SetBackgroundColor(color))?;
 
     print!("  ");
 
@@ -116,55 +417,111 @@ fn draw_tile(pos: Vector, color: Color) -> crossterm::Result<()> {
     Ok(())
 }
 
+// Draw a single tile on the board
+fn draw_tile(pos: Vector, color: Color) -> crossterm::Result<()> {
+    draw_cell((pos.y + 1) as usize, (pos.x + 1) as usize, color)
+}
+
 fn draw_tile_contents(pos: Vector, contents: TileContents) -> crossterm::Result<()> {
     let color = match contents {
         TileContents::Empty => Color::Reset,
         TileContents::Scorched => Color::Red,
+        TileContents::Wall => Color::DarkGrey,
         TileContents::Player(c) => player_term_color(c),
     };
     draw_tile(pos, color)
 }
 
-// Draw the border around the board a certain color
-fn draw_border(color: Color) -> crossterm::Result<()> {
-    execute!(stdout(), RestorePosition, SetBackgroundColor(color))?;
+// Tiles out of sight with fog of war
+const FOG_COLOR: Color = Color::DarkBlue;
+
+// Redraw the whole board as one player sees it, for fog of war
+fn draw_view(view: &PlayerView) -> crossterm::Result<()> {
+    for y in 0..view.board.height() as isize {
+        for x in 0..view.board.width() as isize {
+            let pos = Vector { x, y };
+            match view.tile_contents_at(pos) {
+                Some(contents) => draw_tile_contents(pos, contents)?,
+                None => draw_tile(pos, FOG_COLOR)?,
+            }
+        }
+    }
+    Ok(())
+}
 
-    print!("{}", "  ".repeat(BOARD_SIZE + 2));
+// Draw the border around the board a certain color
+fn draw_border(board: &Board, color: Color) -> crossterm::Result<()> {
+    draw_border_colors(board, color, color)
+}
 
-    for _ in 0..BOARD_SIZE + 1 {
-        execute!(stdout(), MoveDown(1), MoveLeft(2),)?;
+// Draw the border with its top and bottom edges one color and its sides another
+fn draw_border_colors(board: &Board, edges: Color, sides: Color) -> crossterm::Result<()> {
+    let (bottom, right) = (board.height() + 1, board.width() + 1);
 
-        print!("  ");
+    for row in 0..=bottom {
+        for column in 0..=right {
+            if row == 0 || row == bottom {
+                draw_cell(row, column, edges)?;
+            } else if column == 0 || column == right {
+                draw_cell(row, column, sides)?;
+            }
+        }
     }
 
-    execute!(
-        stdout(),
-        RestorePosition,
-        MoveRight(2),
-        SetBackgroundColor(color),
-    )?;
+    Ok(())
+}
 
-    for _ in 0..BOARD_SIZE + 1 {
-        execute!(stdout(), MoveDown(1), MoveLeft(2),)?;
+// Set the border to show whose turn it is, with their color down the sides. In team games the
+// top and bottom are their team's color, otherwise they're the player's color too.
+fn draw_turn_border(board: &Board, player: usize) -> crossterm::Result<()> {
+    let color = player_term_color(board.players()[player].color);
+    let team_color = board.players()[player]
+        .team
+        .and_then(|team| board.team_color(team))
+        .map_or(color, player_term_color);
 
-        print!("  ");
-    }
+    draw_border_colors(board, team_color, color)
+}
 
-    print!("{}", "  ".repeat(BOARD_SIZE + 1));
+// The color to fill the board with when the game is over
+fn outcome_color(board: &Board) -> Color {
+    match board.outcome {
+        GameOutcome::Win { winner, .. } => board
+            .players()
+            .get(winner)
+            .map_or(Color::Grey, |player| player_term_color(player.color)),
+        GameOutcome::TeamWin { team, .. } => {
+            board.team_color(team).map_or(Color::Grey, player_term_color)
+        }
+        GameOutcome::Draw { .. } | GameOutcome::Ongoing => Color::Grey,
+    }
+}
 
-    stdout().flush()?;
+// Fill in the entire board a certain color to show who wins
+fn fill_box(board: &Board, color: Color) -> crossterm::Result<()> {
+    for row in 0..board.height() + 2 {
+        for column in 0..board.width() + 2 {
+            draw_cell(row, column, color)?;
+        }
+    }
 
     Ok(())
 }
 
-// Fill in the entire board a certain color to show who wins
-fn fill_box(color: Color) -> crossterm::Result<()> {
-    execute!(stdout(), RestorePosition, SetBackgroundColor(color))?;
+// Write a line of text underneath the board, like why the game ended
+fn draw_message(board: &Board, message: &str) -> crossterm::Result<()> {
+    execute!(
+        stdout(),
+        RestorePosition,
+        MoveDown(board.height() as u16 + 3),
+        SetBackgroundColor(Color::Reset),
+        // Get rid of any longer message that was there before
+        Clear(ClearType::UntilNewLine),
+    )?;
 
-    for _ in 0..BOARD_SIZE + 2 {
-        print!("{}", "  ".repeat(BOARD_SIZE + 2));
-        execute!(stdout(), MoveDown(1), MoveLeft((BOARD_SIZE as u16 + 2) * 2))?;
-    }
+    print!("{}", message);
+
+    stdout().flush()?;
 
     Ok(())
 }
@@ -172,51 +529,150 @@ fn fill_box(color: Color) -> crossterm::Result<()> {
 enum Keypress {
     Dir(Direction),
     Confirm,
+    Undo,
     Quit,
 }
 
 fn read_key() -> crossterm::Result<Keypress> {
     loop {
-        match crossterm::event::read()? {
-            // Wait for a keypress and only accept it if it's wasd or q
-            Event::Key(KeyEvent {
-                code: KeyCode::Char(c @ ('w' | 'a' | 's' | 'd' | 'q' | ' ')),
-                ..
-            }) => {
-                return Ok(match c {
-                    'w' => Keypress::Dir(Direction::Up),
-                    'a' => Keypress::Dir(Direction::Left),
-                    's' => Keypress::Dir(Direction::Down),
-                    'd' => Keypress::Dir(Direction::Right),
-                    'q' => Keypress::Quit,
-                    ' ' => Keypress::Confirm,
-                    _ => unreachable!(),
-                })
-            }
-            _ => {}
+        // Wait for a keypress and only accept it if it's wasd, yubn, space, backspace or q
+        let Event::Key(KeyEvent { code, .. }) = crossterm::event::read()? else { continue };
+
+        if code == KeyCode::Backspace {
+            return Ok(Keypress::Undo);
+        }
+
+        if let KeyCode::Char(c @ ('w' | 'a' | 's' | 'd' | 'y' | 'u' | 'b' | 'n' | 'q' | ' ')) = code {
+            return Ok(match c {
+                'w' => Keypress::Dir(Direction::Up),
+                'a' => Keypress::Dir(Direction::Left),
+                's' => Keypress::Dir(Direction::Down),
+                'd' => Keypress::Dir(Direction::Right),
+                'y' => Keypress::Dir(Direction::UpLeft),
+                'u' => Keypress::Dir(Direction::UpRight),
+                'b' => Keypress::Dir(Direction::DownLeft),
+                'n' => Keypress::Dir(Direction::DownRight),
+                'q' => Keypress::Quit,
+                ' ' => Keypress::Confirm,
+                _ => unreachable!(),
+            });
         }
     }
 }
 
-fn run(mut b: Board, mut conn: Option<Connection>) -> Result<()> {
+// The human player when playing against the computer
+const HUMAN_PLAYER: usize = 0;
+
+// Today's date as year-month-day, worked out from the system clock (in UTC)
+fn today() -> String {
+    let secs = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+
+    // Convert days since 1970 to a calendar date (Howard Hinnant's civil_from_days)
+    let z = (secs / 86400) as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+// Play a game, carrying on from any moves already in the record. Offline games are saved to
+// save_path if they're quit before the end.
+fn run(
+    mut record: GameRecord,
+    mut conn: Option<Session>,
+    bot: Option<Bot>,
+    record_path: Option<&Path>,
+    save_path: Option<&Path>,
+) -> Result<()> {
+    // Undo records for every move so far, for taking moves back in offline games
+    let mut history: Vec<Undo> = Vec::new();
+
+    let mut b = record.start.clone();
+    let mut played = 0;
+    while played < record.moves.len() {
+        let (count, res) = b.play_recorded(&record.moves[played..])?;
+        history.push(res.undo);
+        played += count;
+    }
+
+    // Moves picked so far this round with simultaneous moves, when everyone is playing here
+    let mut round: Vec<Move> = Vec::new();
+
     setup_drawing(&b)?;
+
+    // With fog of war, only show what the player at this computer can see (or whoever's turn it
+    // is, when everyone is playing here)
+    let local_player = conn
+        .as_ref()
+        .map(|c| c.local_player)
+        .or(bot.map(|_| HUMAN_PLAYER));
+    let draw_fog = |b: &Board| match b.rules.fog {
+        Some(_) => draw_view(&b.view_for(local_player.unwrap_or(b.turn()))),
+        None => Ok(()),
+    };
+    draw_fog(&b)?;
+
+    // Show the seed so the same map can be played again
+    if let Some(seed) = b.seed {
+        draw_message(&b, &format!("map seed {}", seed))?;
+    }
+
+    record.date = Some(today());
+    if bot.is_some() {
+        for (id, name) in record.players.iter_mut().enumerate() {
+            if id != HUMAN_PLAYER {
+                name.push_str(" (computer)");
+            }
+        }
+    }
+
     'main: loop {
-        let i = b.turn;
-        let other_player = (i + 1) % 2;
+        // With simultaneous moves everyone picks a move each round, so online that's always the
+        // player at this computer, and offline everyone still in takes a go in player order
+        let i = match &conn {
+            Some(c) if b.rules.simultaneous => c.local_player,
+            None if b.rules.simultaneous => {
+                b.active_players().nth(round.len()).unwrap_or(b.turn())
+            }
+            _ => b.turn(),
+        };
 
         // Set the border to show the current player's color
-        draw_border(player_term_color(b.players[i].color))?;
+        draw_turn_border(&b, i)?;
+
+        // Moves received over the network could be anything, so don't trust them
+        let remote = conn.as_ref().is_some_and(|c| c.local_player != i);
 
         // loop until a valid move is made
         let mut m: Option<Move> = None;
-        let (next_move, other_player_board): (Move, Option<Board>) = if let Some(c) =
-            conn.as_mut().filter(|c| c.player_num == i)
+        // What the other player says their board's hash is after their move
+        let mut expected_hash = None;
+        let (mover, next_move, update) = if let Some(c) =
+            conn.as_mut().filter(|c| c.local_player != i)
         {
             // If connected to another player and it's their turn, receive their move over the
             // network instead of making the move locally
 
+            // The host gets moves straight from the other players, and everyone else gets
+            // updates from the host. In direct sessions both players get moves straight from
+            // each other.
+            enum Received {
+                Move(MoveMessage),
+                // Boxed since it has a whole board in it
+                Update(Box<UpdateMessage>),
+            }
+
             enum WaitResult {
-                Move(Result<MoveMessage, scorched_earth_network::Error>),
+                // Boxed since a whole board is much bigger than Cancelled
+                Move(Box<Result<Received, scorched_earth_network::Error>>),
                 Cancelled,
             }
 
@@ -232,30 +688,33 @@ fn run(mut b: Board, mut conn: Option<Connection>) -> Result<()> {
                 let (cancel_tx, cancel_rx) = channel();
 
                 s.spawn(move |_| {
-                    tx.send(WaitResult::Move(c.recv_move())).expect("failed to get received move");
+                    let received = if c.is_host() || c.is_direct() {
+                        c.recv_move(i).map(Received::Move)
+                    } else {
+                        c.recv_update().map(|update| Received::Update(Box::new(update)))
+                    };
+                    tx.send(WaitResult::Move(Box::new(received))).expect("failed to get received move");
                     // Tell the other thread to stop now
                     cancel_tx.send(())
                 });
 
                 s.spawn(move |_| {
-                    while let Err(_) = cancel_rx.try_recv() {
+                    while cancel_rx.try_recv().is_err() {
                         if let Ok(true) = crossterm::event::poll(Duration::from_millis(100)) {
-                            match crossterm::event::read() {
-                                // Wait for a keypress and only accept it if it's q
-                                Ok(Event::Key(KeyEvent {
-                                    code: KeyCode::Char('q'),
-                                    ..
-                                })) => {
-                                        tx2.send(WaitResult::Cancelled).expect("Failed to send cancel message from pressing q");
-                                    }
-                                _ => {}
+                            // Wait for a keypress and only accept it if it's q
+                            if let Ok(Event::Key(KeyEvent {
+                                code: KeyCode::Char('q'),
+                                ..
+                            })) = crossterm::event::read()
+                            {
+                                tx2.send(WaitResult::Cancelled).expect("Failed to send cancel message from pressing q");
                             }
                         }
                     }
                 });
 
                 if let Ok(WaitResult::Move(m)) = rx.recv() {
-                    m
+                    *m
                 } else {
                     finish_drawing().expect("Failed to reset terminal");
                     std::process::exit(0);
@@ -263,11 +722,24 @@ fn run(mut b: Board, mut conn: Option<Connection>) -> Result<()> {
             })
             .expect("Failed to join threads")?;
 
-            if other_move.player != i {
-                bail!("Player moved and it isn't their turn!");
+            // Whether it's actually their turn is checked when making the move
+            match other_move {
+                Received::Move(msg) => {
+                    expected_hash = msg.hash;
+                    (msg.player, Some(msg.new_move), None)
+                }
+                Received::Update(update) => (update.player, update.new_move, Some(*update)),
             }
-
-            (other_move.new_move, Some(other_move.new_board.clone()))
+        } else if b.rules.simultaneous && b.is_eliminated(i) {
+            // Players that are out still have to swap moves with everyone else each round, they
+            // just don't have one of their own
+            (i, None, None)
+        } else if let Some(bot) = bot.filter(|_| i != HUMAN_PLAYER) {
+            // Let the computer pick a move for everyone except the human player
+            let Some(bot_move) = bot.choose_move(&b, i) else {
+                bail!("The computer couldn't find a move");
+            };
+            (i, Some(bot_move), None)
         } else {
             // Otherwise preview moves in a loop until one is selected locally
             loop {
@@ -276,61 +748,87 @@ fn run(mut b: Board, mut conn: Option<Connection>) -> Result<()> {
                 // Redraw the tile from the last move preview
                 if let Some(potential_move) = m {
                     for tile in potential_move.tiles_along_path() {
-                        let target_position = b.players[i].pos + tile;
-                        if let Some(contents) = b.tile_contents_at(target_position) {
-                            draw_tile_contents(target_position, contents)?;
+                        if let Some(target_position) = b.translate(b.players()[i].pos, tile) {
+                            if let Some(contents) = b.tile_contents_at(target_position) {
+                                draw_tile_contents(target_position, contents)?;
+                            }
                         }
                     }
                 }
 
                 match key {
                     Keypress::Quit => {
+                        if let (None, Some(path)) = (&conn, save_path) {
+                            if !record.moves.is_empty() {
+                                save::save_game(path, &record, &b)?;
+                                draw_message(&b, &format!("Saved to {}", path.display()))?;
+                            }
+                        }
                         break 'main;
                     }
 
+                    // Taking moves back only makes sense when everyone is playing locally
+                    Keypress::Undo if conn.is_none() => {
+                        // With simultaneous moves, take back the moves picked this round first
+                        if round.pop().is_some() {
+                            continue 'main;
+                        }
+
+                        if history.is_empty() {
+                            continue;
+                        }
+
+                        // Against the computer, take back its moves too so it's the human's
+                        // turn again
+                        while let Some(undo) = history.pop() {
+                            for (pos, contents) in b.unmake_move(undo) {
+                                draw_tile_contents(pos, contents)?;
+                            }
+                            // A simultaneous round has a move from everyone who was still in
+                            let round_len =
+                                if b.rules.simultaneous { b.active_players().count() } else { 1 };
+                            record.moves.truncate(record.moves.len() - round_len);
+
+                            if bot.is_none() || b.turn() == HUMAN_PLAYER {
+                                break;
+                            }
+                        }
+                        draw_fog(&b)?;
+                        continue 'main;
+                    }
+
+                    Keypress::Undo => {}
+
                     Keypress::Confirm => {
                         if let Some(valid_move) =
                             m.filter(|potential_move| b.is_move_valid(i, *potential_move))
                         {
-                            break (valid_move, None);
+                            break (i, Some(valid_move), None);
                         } else {
                             continue;
                         }
                     }
 
                     Keypress::Dir(input_dir) => {
-                        match m.as_mut() {
-                            None => {
-                                m = Some(Move {
-                                    dir: input_dir,
-                                    len: 1,
-                                })
-                            }
-                            Some(old_move) => {
-                                if input_dir == old_move.dir && old_move.len == 1 {
-                                    old_move.len = 2;
-                                } else if input_dir == old_move.dir.opposite() && old_move.len == 2
-                                {
-                                    old_move.len = 1;
-                                } else {
-                                    old_move.len = 1;
-                                    old_move.dir = input_dir;
-                                }
-                            }
+                        // Diagonal keys don't do anything unless the rules allow diagonal moves
+                        // (or it's a hex board, where they go to the diagonal neighbours)
+                        let input_dir = b.topology().from_screen(input_dir);
+                        if b.directions().contains(&input_dir) {
+                            m = Some(b.rules.preview_move(m, input_dir));
                         }
 
                         if let Some(potential_move) = m {
-                            let target_position = b.players[i].pos + potential_move.to_vector();
-                            if (0..BOARD_SIZE as isize).contains(&target_position.x)
-                                && (0..BOARD_SIZE as isize).contains(&target_position.y)
-                            {
+                            let start = b.players()[i].pos;
+                            if b.translate(start, potential_move.to_vector()).is_some() {
                                 let color = if b.is_move_valid(i, potential_move) {
                                     Color::White
                                 } else {
                                     Color::Grey
                                 };
                                 for tile in potential_move.tiles_along_path() {
-                                    draw_tile(b.players[i].pos + tile, color)?;
+                                    if let Some(pos) = b.translate(start, tile) {
+                                        draw_tile(pos, color)?;
+                                    }
                                 }
                             }
                         }
@@ -339,71 +837,194 @@ fn run(mut b: Board, mut conn: Option<Connection>) -> Result<()> {
             }
         };
 
-        let res = b.make_move(i, next_move);
-        for (pos, contents) in res.changes {
-            draw_tile_contents(pos, contents)?;
+        if b.rules.simultaneous {
+            let moves = match conn.as_mut() {
+                // Nobody gets to see anyone else's move until they've all picked one
+                Some(c) => {
+                    let players: Vec<_> = b.active_players().collect();
+                    c.exchange_moves(&players, next_move)?
+                }
+                None => {
+                    // Hide the move from whoever picks next
+                    if let Some(picked) = next_move {
+                        for tile in picked.tiles_along_path() {
+                            if let Some(pos) = b.translate(b.players()[mover].pos, tile) {
+                                if let Some(contents) = b.tile_contents_at(pos) {
+                                    draw_tile_contents(pos, contents)?;
+                                }
+                            }
+                        }
+                        round.push(picked);
+                    }
+                    if round.len() < b.active_players().count() {
+                        continue;
+                    }
+                    std::mem::take(&mut round)
+                }
+            };
+
+            let res = match b.resolve_simultaneous(&moves) {
+                Ok(res) => res,
+                Err(e) if conn.is_some() => bail!("Someone made an illegal move: {}", e),
+                Err(e) => bail!("Illegal move: {}", e),
+            };
+            for (pos, contents) in res.changes {
+                draw_tile_contents(pos, contents)?;
+            }
+            history.push(res.undo);
+            record.moves.extend(moves);
         }
+        // The round has already been played, so there's no single move left to make
+        let next_move = next_move.filter(|_| !b.rules.simultaneous);
 
-        if let Some(b2) = other_player_board {
-            // If the opponent has a board that doesn't match, they're probably cheating or
-            // something
-            if b != b2 {
-                bail!("Other player's board doesn't match!");
+        // Everyone except the host sends their moves to it, and gets an update back once it's
+        // made them
+        let update = match (conn.as_mut(), next_move) {
+            (Some(c), Some(new_move)) if !c.is_host() && !c.is_direct() && update.is_none() => {
+                c.send_move(&MoveMessage {
+                    new_move,
+                    player: mover,
+                    hash: None,
+                })?;
+                Some(c.recv_update()?)
+            }
+            _ => update,
+        };
+
+        // With fog of war only the host has the whole board, so everyone else just goes with
+        // what it shows them
+        let next_move = match &update {
+            Some(update) => update.new_move,
+            None => next_move,
+        };
+
+        if let Some(next_move) = next_move {
+            let res = match b.make_move(mover, next_move) {
+                Ok(res) => res,
+                Err(e) if remote => bail!("Other player made an illegal move: {}", e),
+                Err(e) => bail!("Illegal move: {}", e),
+            };
+            // With fog of war the whole board gets redrawn instead, so nothing out of sight shows
+            if b.rules.fog.is_none() {
+                for (pos, contents) in res.changes {
+                    draw_tile_contents(pos, contents)?;
+                }
             }
+            history.push(res.undo);
+            record.moves.push(next_move);
         }
 
-        if let Some(c) = conn.as_mut() {
-            // If there's another player connected but it's not their turn, send them our move
-            if c.player_num == other_player {
-                c.send_move(MoveMessage {
-                    new_board: b.clone(),
+        if let Some(c) = conn.as_mut().filter(|c| c.is_direct()) {
+            if let Some(next_move) = next_move.filter(|_| !remote) {
+                c.send_move(&MoveMessage {
                     new_move: next_move,
-                    player: i,
+                    player: mover,
+                    hash: Some(b.hash()),
                 })?;
+            } else if expected_hash.is_some_and(|hash| hash != b.hash()) {
+                bail!("Other player's board doesn't match!");
+            }
+        }
+
+        if let Some(update) = update {
+            if update.new_move.is_none() {
+                b = update.view.board;
+            } else if b.hash() != update.view.board.hash() {
+                // If the host has a board that doesn't match, it's probably cheating or something
+                bail!("Host's board doesn't match!");
             }
         }
 
-        if let Some(color) = res.winner {
-            fill_box(player_term_color(color))?;
+        if let Some(c) = conn.as_mut().filter(|c| c.is_host() && !c.is_direct()) {
+            if let Some(next_move) = next_move {
+                c.send_update(&b, mover, next_move)?;
+            }
+        }
+
+        draw_fog(&b)?;
+
+        if b.outcome != GameOutcome::Ongoing {
+            // Now the host can show everyone every move, so they can check it played fair
+            if let Some(c) = conn.as_mut().filter(|c| !c.is_direct() && !b.rules.simultaneous) {
+                if c.is_host() {
+                    c.send_history()?;
+                } else {
+                    record.moves = c.recv_history(&record.start)?;
+                }
+            }
+
+            fill_box(&b, outcome_color(&b))?;
+            draw_message(&b, &b.outcome_summary())?;
             break 'main;
         }
     }
+
+    if let Some(path) = record_path {
+        std::fs::write(path, record.to_string())?;
+    }
+
     Ok(())
 }
 
-fn run_host(addr: &str) -> Result<()> {
-    let mut board = Board::default();
+fn run_host(addr: &str, mut board: Board, record_path: Option<&Path>) -> Result<()> {
     let mut rng = thread_rng();
-    board.turn = if rng.gen_bool(0.5) { 1 } else { 0 };
+    board.set_turn(rng.gen_range(0..board.players().len()));
     let mut secret = [0u8; SECRET_LEN];
     for b in &mut secret {
         *b = rng.sample(Uniform::new_inclusive(b'0', b'9'));
     }
     let secret_string = String::from_utf8_lossy(&secret);
     println!("Hosting game with id: {}", secret_string);
-    let conn = Connection::host(addr, &secret, &board)?;
-    run(board, Some(conn))
+    if board.players().len() > 2 {
+        println!(
+            "Other players join with --seat 1 to --seat {}",
+            board.players().len() - 1
+        );
+    }
+    let conn = Session::host(addr, &secret, &board)?;
+    run(GameRecord::new(board), Some(conn), None, record_path, None)
 }
 
-fn run_join(addr: &str, id: &str) -> Result<()> {
-    let (conn, board) = Connection::conn(addr, id.as_bytes())?;
-    run(board, Some(conn))
+fn run_join(addr: &str, id: &str, seat: usize, record_path: Option<&Path>) -> Result<()> {
+    let (conn, board) = Session::join(addr, id.as_bytes(), seat)?;
+    run(GameRecord::new(board), Some(conn), None, record_path, None)
 }
 
-fn run_offline() -> Result<()> {
-    run(Board::default(), None)
+fn run_offline(
+    game: GameRecord,
+    bot: Option<Bot>,
+    record_path: Option<&Path>,
+    save_path: &Path,
+) -> Result<()> {
+    run(game, None, bot, record_path, Some(save_path))
 }
 
 fn try_main() -> Result<()> {
     let args = Cli::parse();
-    let addr = args.relay.map_or("zorbulator.com:8080".to_string(), |s| {
+    let bot = args.bot();
+    let addr = args.relay.as_ref().map_or("zorbulator.com:8080".to_string(), |s| {
         s.to_str().expect("invalid relay address").to_string()
     });
 
-    match args.command {
-        None => run_offline()?,
-        Some(Commands::Host) => run_host(&addr)?,
-        Some(Commands::Join { id }) => run_join(&addr, id.to_str().expect("invalid ID"))?,
+    let record_path = args.record.as_deref();
+
+    match &args.command {
+        None => {
+            let game = match &args.resume {
+                Some(path) => save::load_game(path)?,
+                None => GameRecord::new(args.start_board(args.players as usize)?),
+            };
+            run_offline(game, bot, record_path, &args.save)?
+        }
+        Some(Commands::Host) => {
+            run_host(&addr, args.start_board(args.players as usize)?, record_path)?
+        }
+        Some(Commands::Join { id, seat }) => {
+            run_join(&addr, id.to_str().expect("invalid ID"), *seat, record_path)?
+        }
+        Some(Commands::Replay { file, speed }) => {
+            replay::run_replay(file, Duration::from_millis(*speed))?
+        }
     }
     Ok(())
 }
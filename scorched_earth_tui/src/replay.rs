@@ -0,0 +1,120 @@
+use std::{path::Path, time::Duration};
+
+use anyhow::Result;
+use crossterm::event::{Event, KeyCode, KeyEvent};
+use scorched_earth_core::{Board, GameOutcome, GameRecord, Undo};
+
+use crate::{
+    draw_border, draw_message, draw_tile_contents, draw_turn_border, outcome_color, setup_drawing,
+};
+
+enum ReplayKey {
+    Forward,
+    Back,
+    // Start or stop playing through the moves automatically
+    Pause,
+    Quit,
+}
+
+// Wait up to the timeout for a replay key, or forever if there's no timeout
+fn poll_replay_key(timeout: Option<Duration>) -> crossterm::Result<Option<ReplayKey>> {
+    loop {
+        if let Some(timeout) = timeout {
+            if !crossterm::event::poll(timeout)? {
+                return Ok(None);
+            }
+        }
+
+        let Event::Key(KeyEvent { code, .. }) = crossterm::event::read()? else { continue };
+
+        return Ok(Some(match code {
+            KeyCode::Char('d') | KeyCode::Right => ReplayKey::Forward,
+            KeyCode::Char('a') | KeyCode::Left => ReplayKey::Back,
+            KeyCode::Char(' ') => ReplayKey::Pause,
+            KeyCode::Char('q') => ReplayKey::Quit,
+            _ => continue,
+        }));
+    }
+}
+
+// Show which move the replay is on, and how the game ended once it gets there
+fn draw_replay_status(board: &Board, shown: usize, total: usize, playing: bool) -> Result<()> {
+    match board.outcome {
+        GameOutcome::Ongoing => draw_turn_border(board, board.turn())?,
+        _ => draw_border(board, outcome_color(board))?,
+    }
+
+    let mut message = format!("move {}/{}", shown, total);
+    if board.outcome != GameOutcome::Ongoing {
+        message.push_str(&format!(" - {}", board.outcome_summary()));
+    }
+    if playing {
+        message.push_str(" (playing)");
+    }
+    draw_message(board, &message)?;
+
+    Ok(())
+}
+
+// Step through a recorded game with a and d (or the arrow keys). Space starts and stops playing
+// through it automatically, with speed as the time between moves.
+pub fn run_replay(path: &Path, speed: Duration) -> Result<()> {
+    let record: GameRecord = std::fs::read_to_string(path)?.parse()?;
+
+    // Make sure every move works before drawing anything
+    record.replay()?;
+
+    let mut b = record.start.clone();
+    setup_drawing(&b)?;
+
+    // Undo records for the moves that are currently shown, along with how many moves each one
+    // took back (more than one for a round of simultaneous moves)
+    let mut history: Vec<(Undo, usize)> = Vec::new();
+    let mut shown = 0;
+    let mut playing = true;
+
+    loop {
+        draw_replay_status(&b, shown, record.moves.len(), playing)?;
+
+        let key = poll_replay_key(playing.then_some(speed))?;
+
+        let forward = match key {
+            // Time for the next move while playing automatically
+            None => true,
+            Some(ReplayKey::Forward) => {
+                playing = false;
+                true
+            }
+            Some(ReplayKey::Back) => {
+                playing = false;
+                false
+            }
+            Some(ReplayKey::Pause) => {
+                playing = !playing;
+                continue;
+            }
+            Some(ReplayKey::Quit) => break,
+        };
+
+        if forward {
+            if shown == record.moves.len() {
+                playing = false;
+                continue;
+            }
+
+            let (count, res) = b.play_recorded(&record.moves[shown..])?;
+            for (pos, contents) in res.changes {
+                draw_tile_contents(pos, contents)?;
+            }
+            history.push((res.undo, count));
+            shown += count;
+        } else if let Some((undo, count)) = history.pop() {
+            for (pos, contents) in b.unmake_move(undo) {
+                draw_tile_contents(pos, contents)?;
+            }
+            shown -= count;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,111 @@
+use std::path::Path;
+
+use anyhow::{bail, Result};
+use scorched_earth_core::{Board, GameRecord, Move};
+use serde::{Deserialize, Serialize};
+
+// Save files start with this line, then the game in MessagePack. Bump the version whenever
+// SavedGame or anything in Board changes, so old saves get rejected instead of read as garbage.
+const SAVE_HEADER: &[u8] = b"scorched earth save v2\n";
+
+#[derive(Serialize, Deserialize)]
+struct SavedGame {
+    start: Board,
+    moves: Vec<Move>,
+    // Where the game got to, to check the moves against
+    board: Board,
+}
+
+// Save an unfinished offline game so it can be resumed later
+pub fn save_game(path: &Path, record: &GameRecord, board: &Board) -> Result<()> {
+    let saved = SavedGame {
+        start: record.start.clone(),
+        moves: record.moves.clone(),
+        board: board.clone(),
+    };
+
+    let mut bytes = SAVE_HEADER.to_vec();
+    bytes.extend(rmp_serde::to_vec(&saved)?);
+    std::fs::write(path, bytes)?;
+
+    Ok(())
+}
+
+// Load a saved game as a record of how it got to where it is
+pub fn load_game(path: &Path) -> Result<GameRecord> {
+    let bytes = std::fs::read(path)?;
+
+    let Some(data) = bytes.strip_prefix(SAVE_HEADER) else {
+        if bytes.starts_with(b"scorched earth save") {
+            bail!("{} was saved by a different version of the game", path.display());
+        }
+        bail!("{} isn't a saved game", path.display());
+    };
+
+    let saved: SavedGame = rmp_serde::from_slice(data)?;
+
+    let mut record = GameRecord::new(saved.start);
+    record.moves = saved.moves;
+
+    if record.replay()? != saved.board {
+        bail!("{} is corrupted: its moves don't lead to its board", path.display());
+    }
+
+    Ok(record)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use scorched_earth_core::Direction;
+
+    use super::*;
+
+    // A file in the temp directory that's deleted again at the end of the test
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str) -> Self {
+            let name = format!("scorched-earth-{}-{}", std::process::id(), name);
+            TempFile(std::env::temp_dir().join(name))
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    #[test]
+    fn save_and_load() {
+        let file = TempFile::new("save-and-load");
+        let mut record = GameRecord::new(Board::default());
+        record.moves.push(Move {
+            dir: Direction::Down,
+            len: 2,
+        });
+        let board = record.replay().unwrap();
+
+        save_game(&file.0, &record, &board).unwrap();
+        let loaded = load_game(&file.0).unwrap();
+        assert_eq!(loaded.moves, record.moves);
+        assert!(loaded.replay().unwrap() == board);
+    }
+
+    #[test]
+    fn old_saves_are_rejected() {
+        let file = TempFile::new("old-save");
+        let mut bytes = b"scorched earth save v1\n".to_vec();
+        bytes.extend(rmp_serde::to_vec(&[1, 2, 3]).unwrap());
+        std::fs::write(&file.0, bytes).unwrap();
+
+        let error = load_game(&file.0).err().unwrap().to_string();
+        assert!(error.ends_with("was saved by a different version of the game"), "{error}");
+
+        std::fs::write(&file.0, "not a save").unwrap();
+        let error = load_game(&file.0).err().unwrap().to_string();
+        assert!(error.ends_with("isn't a saved game"), "{error}");
+    }
+}
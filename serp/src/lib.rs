@@ -1,4 +1,4 @@
-use std::{net::{ToSocketAddrs, TcpStream}, io::{Write, BufReader, BufRead}, time::Duration};
+use std::{net::{ToSocketAddrs, TcpStream}, io::{self, Read, Write}, time::Duration};
 
 #[derive(thiserror::Error, Debug)]
 pub enum SerpError {
@@ -25,10 +25,7 @@ pub fn host<A: ToSocketAddrs>(addr: A, id: &str) -> Result<TcpStream, SerpError>
     // Server should respond with "ok" soon
     stream.set_read_timeout(Some(Duration::from_secs(5)))?;
 
-    let mut reader = BufReader::new(stream);
-    let mut buf = String::new();
-
-    reader.read_line(&mut buf).map_err(|_| SerpError::NoOk)?;
+    let mut buf = read_line(&mut stream).map_err(|_| SerpError::NoOk)?;
  
     match buf.as_str() {
         "ok\n" => {},
@@ -38,13 +35,12 @@ pub fn host<A: ToSocketAddrs>(addr: A, id: &str) -> Result<TcpStream, SerpError>
     }
 
     // The connection could take a lot longer, so disable timeout
-    reader.get_mut().set_read_timeout(None)?;
+    stream.set_read_timeout(None)?;
 
-    buf.clear();
-    reader.read_line(&mut buf)?;
+    buf = read_line(&mut stream)?;
 
     match buf.as_str() {
-        "connected\n" => Ok(reader.into_inner()),
+        "connected\n" => Ok(stream),
         _ => Err(SerpError::ConnectionBroke),
     }
 }
@@ -60,10 +56,7 @@ pub fn conn<A: ToSocketAddrs>(addr: A, id: &str) -> Result<TcpStream, SerpError>
     // Server should respond with "ok" soon
     stream.set_read_timeout(Some(Duration::from_secs(5)))?;
 
-    let mut reader = BufReader::new(stream);
-    let mut buf = String::new();
-
-    reader.read_line(&mut buf).map_err(|_| SerpError::NoOk)?;
+    let mut buf = read_line(&mut stream).map_err(|_| SerpError::NoOk)?;
 
     match buf.as_str() {
         "ok\n" => {},
@@ -72,14 +65,27 @@ pub fn conn<A: ToSocketAddrs>(addr: A, id: &str) -> Result<TcpStream, SerpError>
         _ => { return Err(SerpError::NoOk); },
     }
 
-    buf.clear();
-    reader.read_line(&mut buf)?;
+    buf = read_line(&mut stream)?;
 
     // Connect should be fast so don't unset timeout until afterwards
-    reader.get_mut().set_read_timeout(None)?;
+    stream.set_read_timeout(None)?;
 
     match buf.as_str() {
-        "connected\n" => Ok(reader.into_inner()),
+        "connected\n" => Ok(stream),
         _ => Err(SerpError::ConnectionBroke),
     }
 }
+
+// Read a line a byte at a time, since the other player's messages can come straight after it and
+// a buffered reader would swallow them
+fn read_line(stream: &mut TcpStream) -> io::Result<String> {
+    let mut line = Vec::new();
+    let mut byte = [0u8; 1];
+    while line.last() != Some(&b'\n') {
+        if stream.read(&mut byte)? == 0 {
+            break;
+        }
+        line.push(byte[0]);
+    }
+    Ok(String::from_utf8_lossy(&line).into_owned())
+}
@@ -84,7 +84,9 @@ async fn process(mut tcp_stream: TcpStream) -> Result<(), anyhow::Error> {
                 }
             }
 
-            let Some(mut other_stream) = MAP.get_mut(&id.to_string()) else {
+            // Take the host's stream out of the map rather than holding on to the entry, which
+            // would lock up other rooms in the same shard for the whole game
+            let Some((_, mut other_stream)) = MAP.remove(id) else {
                 stream.write(b"fail\n").await?;
                 stream.flush().await?;
                 bail!("tried to join nonexistent room");
@@ -97,9 +99,6 @@ async fn process(mut tcp_stream: TcpStream) -> Result<(), anyhow::Error> {
             other_stream.write(b"connected\n").await?;
             pipe(&mut tcp_stream, &mut other_stream).await?;
             info!("closing {}", id);
-            drop(tcp_stream);
-            drop(other_stream);
-            MAP.remove(id);
         }
         _ => unreachable!(),
     }